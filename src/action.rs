@@ -1,9 +1,10 @@
 use core::panic;
-use std::u8;
 
 use crate::{
     bit_operations::shifts::{set_0_at_index, set_1_at_index},
     board::{pieces, BoardData, Squares, ZOBRIST_TABLES},
+    eval::{PHASE_WEIGHTS, PIECE_SQUARE_TABLES},
+    fen::idx_to_coordsquare,
 };
 
 /* == == == == == == == == == == == == == == == == == == == == == == == == == == ==
@@ -71,6 +72,9 @@ pub trait Move {
     fn move_type(&self) -> u16;
 
     fn promote_to(&self) -> u16;
+
+    // long algebraic notation, as used by UCI: e2e4, e7e8q, e1g1
+    fn to_uci(&self) -> String;
 }
 
 impl Move for Action {
@@ -89,6 +93,19 @@ impl Move for Action {
     fn promote_to(&self) -> u16 {
         (self >> 14) & 0b11
     }
+
+    fn to_uci(&self) -> String {
+        let mut uci = idx_to_coordsquare(self.move_from()) + &idx_to_coordsquare(self.move_to());
+        if self.move_type() == actions::PROMOTION {
+            uci.push(match self.promote_to() {
+                actions::PR_QUEEN => 'q',
+                actions::PR_KNIGHT => 'n',
+                actions::PR_BISHOP => 'b',
+                _ => 'r',
+            });
+        }
+        uci
+    }
 }
 
 impl BoardData {
@@ -100,14 +117,12 @@ impl BoardData {
 
         let tag = action.move_type();
         let mut savestate = StateData::new(self);
+        let mut double_push = None;
 
         // default actions are performed
-        self.half_move_counter += 1;
+        self.half_move_counter = self.half_move_counter.saturating_add(1);
         // set the passant square to none in the zobrist key
-        self.zobrist_key ^= match self.passant_square {
-            None => 0,
-            Some(sqr) => (sqr & 7) as u64,
-        };
+        self.zobrist_key ^= self.passant_key();
 
         self.zobrist_key ^= ZOBRIST_TABLES.castling_rights[self.castle_rights_mask as usize];
         // undos the castle mask
@@ -120,49 +135,28 @@ impl BoardData {
 
                 if maybe_piececapture != pieces::NOPIECE {
                     savestate.set_captured(maybe_piececapture); // a piece is captured, so the state must be saved
-                    set_0_at_index(moveto, &mut self.bitboards[maybe_piececapture as usize]);
+                    self.removepiece(moveto);
+                    self.remove_rook_rights(moveto);
                     self.half_move_counter = 0; // a capture resets 50 move counter
                 }
-                //println!("Movefrom: {}, Moveto: {}", movefrom, moveto);
                 self.move_piece(movefrom, moveto);
 
                 // update the board data, with castle rights and en passant
                 match movingpiece {
                     pieces::WPAWN => {
                         if moveto - movefrom == 16 {
-                            // it is a doublemove, so we update the possible en passant square
-                            self.passant_square = Some(moveto - 8);
-                            // update zobrist key:
-                            self.zobrist_key ^=
-                                ZOBRIST_TABLES.passant_square[(moveto & 7) as usize];
+                            // it is a doublemove, so we note the possible en passant square
+                            double_push = Some(moveto - 8);
                         }
                         self.half_move_counter = 0; // a pawn move resets hmc
                     }
                     pieces::BPAWN => {
                         if movefrom - moveto == 16 {
-                            self.passant_square = Some(moveto + 8);
-                            self.zobrist_key ^=
-                                ZOBRIST_TABLES.passant_square[(moveto & 7) as usize];
+                            double_push = Some(moveto + 8);
                         }
                         self.half_move_counter = 0;
                     }
-                    pieces::WKING => {
-                        self.castle_rights_mask &= 0b0011; // sets the first two bits, WK and WQ to 0, as the king has moved
-                    }
-                    pieces::BKING => {
-                        self.castle_rights_mask &= 0b1100; // sets the last two bits, BK and BQ to 0
-                    }
-                    pieces::WROOK => match movefrom {
-                        0 => self.castle_rights_mask &= 0b1011, // sets WQ to 0, rook has moved from starting square
-                        7 => self.castle_rights_mask &= 0b0111, // sets WK to 0
-                        _ => (),
-                    },
-                    pieces::BROOK => match movefrom {
-                        63 => self.castle_rights_mask &= 0b1101, // sets BK to 0
-                        46 => self.castle_rights_mask &= 0b0110, // sets BQ to 0
-                        _ => (),
-                    },
-                    _ => (),
+                    _ => self.remove_mover_rights(movingpiece, movefrom),
                 }
             }
 
@@ -173,7 +167,9 @@ impl BoardData {
                 if maybe_piececapture != pieces::NOPIECE {
                     savestate.set_captured(maybe_piececapture);
                     self.removepiece(moveto);
+                    self.remove_rook_rights(moveto);
                 }
+                self.half_move_counter = 0; // promotions are pawn moves
                 let tomovetag = if self.to_move { 0 } else { 6 };
                 let promote_to = match action.promote_to() {
                     actions::PR_QUEEN => pieces::WQUEEN + tomovetag,
@@ -220,7 +216,8 @@ impl BoardData {
                 self.castle_rights_mask &= removemask;
             }
             actions::PASSANT => {
-                let mut passantsq = match self.passant_square {
+                self.passant_square = None;
+                let mut passantsq = match savestate.passant_square {
                     Some(square) => square,
                     None => panic!("Invalid En Passant!"),
                 };
@@ -239,15 +236,56 @@ impl BoardData {
         }
 
         self.zobrist_key ^= ZOBRIST_TABLES.castling_rights[self.castle_rights_mask as usize];
+        self.zobrist_key ^= ZOBRIST_TABLES.to_move;
+        if !self.to_move {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+        self.key_history.push(savestate.zobrist_key);
+        self.prev_states.push(savestate);
+        self.to_move = !self.to_move;
+
+        // the passant square is only kept if the opponent can take on it
+        if double_push.is_some() {
+            self.passant_square = double_push;
+            if self.can_capture_passant() {
+                self.zobrist_key ^= self.passant_key();
+            } else {
+                self.passant_square = None;
+            }
+        }
+    }
+
+    // passes the turn without moving a piece, for null move pruning.
+    // must not be played while in check
+    pub fn make_null_move(&mut self) {
+        let savestate = StateData::new(self);
+
+        self.half_move_counter = self.half_move_counter.saturating_add(1);
+        self.zobrist_key ^= self.passant_key();
+        self.passant_square = None;
+        self.zobrist_key ^= ZOBRIST_TABLES.to_move;
+        if !self.to_move {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+        self.key_history.push(savestate.zobrist_key);
         self.prev_states.push(savestate);
         self.to_move = !self.to_move;
     }
 
+    pub fn undo_null_move(&mut self) {
+        if let Some(state) = self.prev_states.pop() {
+            self.key_history.pop();
+            state.set_self(self);
+            self.to_move = !self.to_move;
+        }
+    }
+
     pub fn undo_move(&mut self, action: Action) {
         let undo = match self.prev_states.pop() {
             Some(state) => state,
             None => return,
         };
+        self.key_history.pop();
 
         let movefrom = action.move_from();
         let moveto = action.move_to();
@@ -297,13 +335,13 @@ impl BoardData {
                 }
                 62 => {
                     //g8
-                    rookfrom = Squares::h1;
-                    rookto = Squares::f1;
+                    rookfrom = Squares::h8;
+                    rookto = Squares::f8;
                 }
                 58 => {
-                    //f8
-                    rookfrom = Squares::a1;
-                    rookto = Squares::d1;
+                    //c8
+                    rookfrom = Squares::a8;
+                    rookto = Squares::d8;
                 }
                 _ => panic!("bad castle"),
             }
@@ -314,6 +352,29 @@ impl BoardData {
         self.to_move = !self.to_move
     }
 
+    // a king or rook that leaves its starting square can no longer castle
+    #[inline]
+    fn remove_mover_rights(&mut self, piece: u8, square: u8) {
+        match piece {
+            pieces::WKING => self.castle_rights_mask &= 0b0011, // WK and WQ
+            pieces::BKING => self.castle_rights_mask &= 0b1100, // BK and BQ
+            pieces::WROOK | pieces::BROOK => self.remove_rook_rights(square),
+            _ => (),
+        }
+    }
+
+    // a rook captured on its starting square can no longer castle
+    #[inline]
+    fn remove_rook_rights(&mut self, square: u8) {
+        match square {
+            0 => self.castle_rights_mask &= 0b1011, // WQ
+            7 => self.castle_rights_mask &= 0b0111, // WK
+            56 => self.castle_rights_mask &= 0b1110, // BQ
+            63 => self.castle_rights_mask &= 0b1101, // BK
+            _ => (),
+        }
+    }
+
     #[inline]
     fn move_piece(&mut self, start_square: u8, end_square: u8) {
         let moving_piece = self.mailbox[start_square as usize];
@@ -321,41 +382,114 @@ impl BoardData {
 
         set_0_at_index(start_square, moving_bitboard);
         set_1_at_index(end_square, moving_bitboard);
+        let occupancy = &mut self.bitboards[color_occupancy(moving_piece)];
+        set_0_at_index(start_square, occupancy);
+        set_1_at_index(end_square, occupancy);
         self.mailbox[start_square as usize] = pieces::NOPIECE;
         self.mailbox[end_square as usize] = moving_piece;
 
         self.zobrist_key ^=
             ZOBRIST_TABLES.piecesquares[start_square as usize][moving_piece as usize];
         self.zobrist_key ^= ZOBRIST_TABLES.piecesquares[end_square as usize][moving_piece as usize];
+        if moving_piece % 6 == pieces::WPAWN {
+            self.pawn_key ^=
+                ZOBRIST_TABLES.piecesquares[start_square as usize][moving_piece as usize];
+            self.pawn_key ^=
+                ZOBRIST_TABLES.piecesquares[end_square as usize][moving_piece as usize];
+        }
+        let table = &PIECE_SQUARE_TABLES[moving_piece as usize];
+        self.psq_score += table[end_square as usize] - table[start_square as usize];
     }
 
-    // having a specific function for captures should speed up quiescence search
+    // having a specific function for captures should speed up quiescence search.
+    // only for NORMAL moves onto an enemy piece, en passant and promotions
+    // go through make_move
     pub fn do_capture(&mut self, action: Action) {
-        unimplemented!()
+        let movefrom = action.move_from();
+        let moveto = action.move_to();
+        let captured = self.mailbox[moveto as usize];
+        debug_assert!(action.move_type() == actions::NORMAL && captured != pieces::NOPIECE);
+
+        let mut savestate = StateData::new(self);
+        savestate.set_captured(captured);
+
+        self.zobrist_key ^= self.passant_key();
+        self.passant_square = None;
+        self.zobrist_key ^= ZOBRIST_TABLES.castling_rights[self.castle_rights_mask as usize];
+        self.remove_mover_rights(self.mailbox[movefrom as usize], movefrom);
+        self.remove_rook_rights(moveto);
+        self.zobrist_key ^= ZOBRIST_TABLES.castling_rights[self.castle_rights_mask as usize];
+
+        self.removepiece(moveto);
+        self.move_piece(movefrom, moveto);
+        self.half_move_counter = 0;
+
+        self.zobrist_key ^= ZOBRIST_TABLES.to_move;
+        if !self.to_move {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+        self.key_history.push(savestate.zobrist_key);
+        self.prev_states.push(savestate);
+        self.to_move = !self.to_move;
+    }
+
+    pub fn undo_capture(&mut self, action: Action) {
+        let undo = match self.prev_states.pop() {
+            Some(state) => state,
+            None => return,
+        };
+        self.key_history.pop();
+        self.move_piece(action.move_to(), action.move_from());
+        self.set_piece(action.move_to(), undo.captured_piece);
+        undo.set_self(self);
+        self.to_move = !self.to_move;
     }
     // DONT USE IF THERES A PIECE ALREADY AT THE INDEX
     #[inline]
     fn set_piece(&mut self, square: u8, piece: u8) {
         self.mailbox[square as usize] = piece;
         set_1_at_index(square, &mut self.bitboards[piece as usize]);
+        set_1_at_index(square, &mut self.bitboards[color_occupancy(piece)]);
         self.zobrist_key ^= ZOBRIST_TABLES.piecesquares[square as usize][piece as usize];
+        if piece % 6 == pieces::WPAWN {
+            self.pawn_key ^= ZOBRIST_TABLES.piecesquares[square as usize][piece as usize];
+        }
+        self.psq_score += PIECE_SQUARE_TABLES[piece as usize][square as usize];
+        self.phase += PHASE_WEIGHTS[(piece % 6) as usize];
     }
 
     #[inline]
     fn removepiece(&mut self, square: u8) {
         let piece = self.mailbox[square as usize];
         set_0_at_index(square, &mut self.bitboards[piece as usize]);
+        set_0_at_index(square, &mut self.bitboards[color_occupancy(piece)]);
         self.mailbox[square as usize] = pieces::NOPIECE;
         self.zobrist_key ^= ZOBRIST_TABLES.piecesquares[square as usize][piece as usize];
+        if piece % 6 == pieces::WPAWN {
+            self.pawn_key ^= ZOBRIST_TABLES.piecesquares[square as usize][piece as usize];
+        }
+        self.psq_score -= PIECE_SQUARE_TABLES[piece as usize][square as usize];
+        self.phase -= PHASE_WEIGHTS[(piece % 6) as usize];
+    }
+}
+
+// index of the occupancy bitboard for a piece's color
+#[inline]
+fn color_occupancy(piece: u8) -> usize {
+    if piece < pieces::BKING {
+        pieces::ALLWHITE as usize
+    } else {
+        pieces::ALLBLACK as usize
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StateData {
     captured_piece: u8,
     passant_square: Option<u8>,
     castlemask: u8,
-    halfmove_ctr: u8,
+    halfmove_ctr: u16,
+    fullmove_number: u16,
     zobrist_key: u64,
 }
 
@@ -366,6 +500,7 @@ impl StateData {
             passant_square: board.passant_square,
             castlemask: board.castle_rights_mask,
             halfmove_ctr: board.half_move_counter,
+            fullmove_number: board.fullmove_number,
             zobrist_key: board.zobrist_key,
         }
     }
@@ -379,6 +514,98 @@ impl StateData {
         board.castle_rights_mask = self.castlemask;
         board.passant_square = self.passant_square;
         board.half_move_counter = self.halfmove_ctr;
+        board.fullmove_number = self.fullmove_number;
         board.zobrist_key = self.zobrist_key;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BK, WK, WQ};
+    use crate::fen::{from_fen, startpos};
+
+    // the incremental state against the same state worked out from the pieces
+    fn assert_fresh(board: &BoardData) {
+        assert_eq!(
+            board.zobrist_key,
+            board.compute_zobristkey(),
+            "{}",
+            board.to_fen()
+        );
+        let white = (pieces::WKING..=pieces::WPAWN)
+            .fold(0, |all, piece| all | board.bitboards[piece as usize]);
+        let black = (pieces::BKING..=pieces::BPAWN)
+            .fold(0, |all, piece| all | board.bitboards[piece as usize]);
+        assert_eq!(board.bitboards[pieces::ALLWHITE as usize], white);
+        assert_eq!(board.bitboards[pieces::ALLBLACK as usize], black);
+    }
+
+    fn play(board: &mut BoardData, move_str: &str) -> Action {
+        let action = board.parse_uci_move(move_str).unwrap();
+        board.make_move(action);
+        assert_fresh(board);
+        action
+    }
+
+    #[test]
+    fn keys_and_occupancy_follow_every_move() {
+        // a double push sets the passant key and the next move clears it,
+        // and every move flips the side to move key
+        let mut board = startpos();
+        for move_str in [
+            "e2e4", "d7d5", "e4d5", "c7c5", "d5c6", "b8c6", "g1f3", "g8f6",
+        ] {
+            play(&mut board, move_str);
+        }
+        assert_eq!(
+            board.to_fen(),
+            "r1bqkb1r/pp2pppp/2n2n2/8/8/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 5"
+        );
+    }
+
+    #[test]
+    fn captures_remove_the_captured_piece() {
+        let mut board = from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let action = play(&mut board, "e4d5");
+        assert_eq!(board.bitboards[pieces::BPAWN as usize], 0);
+        assert_eq!(board.bitboards[pieces::ALLBLACK as usize], 1 << 60);
+        board.undo_move(action);
+        assert_fresh(&board);
+        assert_eq!(board.to_fen(), "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn rook_moves_remove_their_castle_right() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        let mut board = from_fen(fen).unwrap();
+        play(&mut board, "a8b8");
+        assert_eq!(board.castle_rights_mask, WK | WQ | BK);
+        play(&mut board, "h1h8");
+        assert_eq!(board.castle_rights_mask, WQ);
+    }
+
+    #[test]
+    fn promotions_reset_the_halfmove_clock() {
+        let mut board = from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 10 40").unwrap();
+        play(&mut board, "b7b8q");
+        assert_eq!(board.half_move_counter, 0);
+        assert_eq!(board.to_fen(), "1Q2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+    }
+
+    #[test]
+    fn castling_is_undone_for_both_sides() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        for (white_castle, black_castle) in [("e1g1", "e8c8"), ("e1c1", "e8g8")] {
+            let mut board = from_fen(fen).unwrap();
+            let white = play(&mut board, white_castle);
+            let black = play(&mut board, black_castle);
+            assert_eq!(board.castle_rights_mask, 0);
+            board.undo_move(black);
+            assert_fresh(&board);
+            board.undo_move(white);
+            assert_fresh(&board);
+            assert_eq!(board.to_fen(), fen);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::bit_operations::{ls1b, pop_ls1b, shifts};
+use crate::bitboard_gen::{
+    bishop_attacks, rook_attacks, KING_TABLES, KNIGHT_TABLES, PAWN_CAPTURE_TABLES,
+};
+use crate::board::{pieces, BoardData};
+
+/* ========================================
+*   Attack queries. A piece "attacks" a
+|   square if it could capture an enemy
+*   piece standing there, regardless of
+|   whether the capture would be legal.
+*   Sides are passed as bools, the same as
+|   BoardData::to_move: true means white.
+   ======================================   */
+
+impl BoardData {
+    #[inline]
+    pub fn occupancy(&self) -> u64 {
+        self.bitboards[pieces::ALLWHITE as usize] | self.bitboards[pieces::ALLBLACK as usize]
+    }
+
+    #[inline]
+    pub fn king_square(&self, side: bool) -> u8 {
+        let idx = if side { pieces::WKING } else { pieces::BKING };
+        ls1b(self.bitboards[idx as usize])
+    }
+
+    // bitboard of every piece of either side attacking the square.
+    // occupancy is passed in so that callers can x-ray through pieces
+    pub fn attackers_to(&self, square: u8, occupancy: u64) -> u64 {
+        let bb = &self.bitboards;
+        let sq = square as usize;
+        let diagonals = bb[pieces::WBISHOP as usize]
+            | bb[pieces::BBISHOP as usize]
+            | bb[pieces::WQUEEN as usize]
+            | bb[pieces::BQUEEN as usize];
+        let orthogonals = bb[pieces::WROOK as usize]
+            | bb[pieces::BROOK as usize]
+            | bb[pieces::WQUEEN as usize]
+            | bb[pieces::BQUEEN as usize];
+
+        // a white pawn attacks the square if a black pawn on the square would attack it
+        (PAWN_CAPTURE_TABLES[1][sq] & bb[pieces::WPAWN as usize])
+            | (PAWN_CAPTURE_TABLES[0][sq] & bb[pieces::BPAWN as usize])
+            | (KNIGHT_TABLES[sq] & (bb[pieces::WKNIGHT as usize] | bb[pieces::BKNIGHT as usize]))
+            | (KING_TABLES[sq] & (bb[pieces::WKING as usize] | bb[pieces::BKING as usize]))
+            | (bishop_attacks(occupancy, square) & diagonals)
+            | (rook_attacks(occupancy, square) & orthogonals)
+    }
+
+    pub fn is_square_attacked(&self, square: u8, by_side: bool) -> bool {
+        let side_pieces = if by_side {
+            self.bitboards[pieces::ALLWHITE as usize]
+        } else {
+            self.bitboards[pieces::ALLBLACK as usize]
+        };
+        self.attackers_to(square, self.occupancy()) & side_pieces != 0
+    }
+
+    // the pieces giving check to the side to move
+    pub fn checkers(&self) -> u64 {
+        let enemy = if self.to_move {
+            self.bitboards[pieces::ALLBLACK as usize]
+        } else {
+            self.bitboards[pieces::ALLWHITE as usize]
+        };
+        self.attackers_to(self.king_square(self.to_move), self.occupancy()) & enemy
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.is_square_attacked(self.king_square(self.to_move), !self.to_move)
+    }
+
+    // every square attacked by a side, given an occupancy
+    pub fn attacked_squares(&self, by_side: bool, occupancy: u64) -> u64 {
+        let offset = if by_side { 0 } else { 6 };
+        let bb = &self.bitboards;
+        let pawns = bb[(pieces::WPAWN + offset) as usize];
+        let mut attacked = if by_side {
+            shifts::shift_nw(pawns) | shifts::shift_ne(pawns)
+        } else {
+            shifts::shift_sw(pawns) | shifts::shift_se(pawns)
+        };
+
+        let mut knights = bb[(pieces::WKNIGHT + offset) as usize];
+        while knights != 0 {
+            attacked |= KNIGHT_TABLES[pop_ls1b(&mut knights) as usize];
+        }
+        let queens = bb[(pieces::WQUEEN + offset) as usize];
+        let mut diagonals = bb[(pieces::WBISHOP + offset) as usize] | queens;
+        while diagonals != 0 {
+            attacked |= bishop_attacks(occupancy, pop_ls1b(&mut diagonals));
+        }
+        let mut orthogonals = bb[(pieces::WROOK + offset) as usize] | queens;
+        while orthogonals != 0 {
+            attacked |= rook_attacks(occupancy, pop_ls1b(&mut orthogonals));
+        }
+        let mut kings = bb[(pieces::WKING + offset) as usize];
+        while kings != 0 {
+            attacked |= KING_TABLES[pop_ls1b(&mut kings) as usize];
+        }
+        attacked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Move;
+    use crate::bit_operations::generate_from_index;
+    use crate::board::pieces;
+    use crate::fen::{from_fen, startpos};
+    use crate::notation::square_from_str;
+    use crate::testing::KIWIPETE;
+
+    fn sqr(name: &str) -> u8 {
+        square_from_str(name).unwrap()
+    }
+
+    fn squares(names: &[&str]) -> u64 {
+        names
+            .iter()
+            .fold(0, |board, name| board | generate_from_index(sqr(name)))
+    }
+
+    #[test]
+    fn attackers_of_both_sides() {
+        let kiwipete = from_fen(KIWIPETE).unwrap();
+        let occupancy = kiwipete.occupancy();
+        assert_eq!(
+            kiwipete.attackers_to(sqr("d5"), occupancy),
+            squares(&["e4", "c3", "e6", "b6", "f6"])
+        );
+        // the knight on e5 is neither attacked nor defended
+        assert_eq!(kiwipete.attackers_to(sqr("e5"), occupancy), 0);
+        // the bishop on g7 sees e5 once the knight on f6 is gone
+        assert_eq!(
+            kiwipete.attackers_to(sqr("e5"), occupancy & !squares(&["f6"])),
+            squares(&["g7"])
+        );
+        assert!(kiwipete.is_square_attacked(sqr("d5"), true));
+        assert!(kiwipete.is_square_attacked(sqr("d5"), false));
+        assert!(!kiwipete.is_square_attacked(sqr("e5"), false));
+        assert_eq!(kiwipete.checkers(), 0);
+        assert!(!kiwipete.in_check());
+    }
+
+    #[test]
+    fn double_check() {
+        let board = from_fen("4r2k/8/8/8/8/3n4/8/4K3 w - - 0 1").unwrap();
+        assert!(board.in_check());
+        assert_eq!(board.checkers(), squares(&["e8", "d3"]));
+
+        let mut moves = Vec::new();
+        board.generate_legal_moves(&mut moves);
+        // only the king can answer two checks at once
+        assert!(!moves.is_empty());
+        assert!(moves
+            .iter()
+            .all(|action| board.mailbox[action.move_from() as usize] == pieces::WKING));
+    }
+
+    #[test]
+    fn attacked_squares_xray_the_king() {
+        assert_eq!(
+            startpos().attacked_squares(true, startpos().occupancy()),
+            0x00ff_ff7e
+        );
+
+        let board = from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        let king = board.bitboards[pieces::WKING as usize];
+        let f1 = squares(&["f1"]);
+        // the king blocks the rook, but stepping along the rank doesn't escape it
+        assert_eq!(board.attacked_squares(false, board.occupancy()) & f1, 0);
+        assert_ne!(
+            board.attacked_squares(false, board.occupancy() & !king) & f1,
+            0
+        );
+        assert!(board.parse_uci_move("e1f1").is_err());
+        assert!(board.parse_uci_move("e1e2").is_ok());
+    }
+}
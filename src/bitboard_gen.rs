@@ -26,7 +26,7 @@ const EDGES: u64 = 0xff818181818181ff;
 #[allow(clippy::needless_range_loop)]
 pub fn generate_king_moves() -> [u64; 64] {
     let mut store_val = [0; 64];
-    for i in 0..63 {
+    for i in 0..64 {
         store_val[i] = king_moves_for_square(i as u8);
     }
     store_val
@@ -35,7 +35,7 @@ pub fn generate_king_moves() -> [u64; 64] {
 #[allow(clippy::needless_range_loop)]
 pub fn generate_knight_moves() -> [u64; 64] {
     let mut store_val = [0; 64];
-    for i in 0..63 {
+    for i in 0..64 {
         store_val[i] = knight_moves_for_square(i as u8);
     }
     store_val
@@ -88,6 +88,7 @@ pub fn pawn_moves_for_square(index: u8, color: bool) -> u64 {
 }
 
 
+#[allow(clippy::needless_range_loop)]
 pub fn generate_pawncaptures() -> [[u64; 64]; 2] {
     let mut store_val = [[0; 64]; 2];
     for i in 0..64 {
@@ -310,12 +311,12 @@ pub fn generate_bishoptables() -> [u64; 64] {
 pub fn generate_all_blookups() -> Box<[[u64; 512]; 64]> {
     let mut vals = vec![[0; 512]; 64];
     for i in 0..64 {
-        let mask = BISHOP_MASKS[i as usize];
+        let mask = BISHOP_MASKS[i];
         let permutations = 1 << mask.count_ones();
         for j in 0..permutations {
             let blockerboard = generate_permutation(mask, j);
             let key = transform(blockerboard, BMAGICS[i], mask.count_ones());
-            vals[i as usize][key as usize] = to_moveboard(blockerboard, i as u8, false);
+            vals[i][key as usize] = to_moveboard(blockerboard, i as u8, false);
         }
     }
 
@@ -339,12 +340,12 @@ pub fn generate_all_bperms(square: u8) -> [u64; 512] {
 pub fn generate_all_rlookups() -> Box<[[u64; 4096]; 64]> {
     let mut vals = vec![[0; 4096]; 64];
     for i in 0..64 {
-        let mask = ROOK_MASKS[i as usize];
+        let mask = ROOK_MASKS[i];
         let permutations = 1 << mask.count_ones();
         for j in 0..permutations {
             let blockerboard = generate_permutation(mask, j);
             let key = transform(blockerboard, RMAGICS[i], mask.count_ones());
-            vals[i as usize][key as usize] = to_moveboard(blockerboard, i as u8, true);
+            vals[i][key as usize] = to_moveboard(blockerboard, i as u8, true);
         }
     }
 
@@ -436,4 +437,56 @@ pub fn generate_blockermask(square: u8, orientation: bool) -> u64 {
     } else {
         to_moveboard(0, square, false) & (!EDGES)
     }
-}
\ No newline at end of file
+}
+
+/* ========================================
+*   BETWEEN_TABLES holds the squares strictly
+|   between two squares on a shared rank,
+*   file or diagonal. LINE_TABLES holds the
+|   entire line through both squares. Both
+*   are empty if the squares are not aligned.
+|   They are used for check and pin masks.
+   ======================================   */
+
+lazy_static! {
+    pub static ref BETWEEN_TABLES: Box<[[u64; 64]; 64]> = generate_between_tables();
+    pub static ref LINE_TABLES: Box<[[u64; 64]; 64]> = generate_line_tables();
+}
+
+pub fn generate_between_tables() -> Box<[[u64; 64]; 64]> {
+    let mut vals = Box::new([[0; 64]; 64]);
+    for from in 0..64u8 {
+        for to in 0..64u8 {
+            let to_board = generate_from_index(to);
+            let from_board = generate_from_index(from);
+            if rook_attacks(0, from) & to_board != 0 {
+                vals[from as usize][to as usize] =
+                    rook_attacks(to_board, from) & rook_attacks(from_board, to);
+            } else if bishop_attacks(0, from) & to_board != 0 {
+                vals[from as usize][to as usize] =
+                    bishop_attacks(to_board, from) & bishop_attacks(from_board, to);
+            }
+        }
+    }
+    vals
+}
+
+pub fn generate_line_tables() -> Box<[[u64; 64]; 64]> {
+    let mut vals = Box::new([[0; 64]; 64]);
+    for from in 0..64u8 {
+        for to in 0..64u8 {
+            let ends = generate_from_index(from) | generate_from_index(to);
+            if from == to {
+                continue;
+            }
+            if rook_attacks(0, from) & generate_from_index(to) != 0 {
+                vals[from as usize][to as usize] =
+                    (rook_attacks(0, from) & rook_attacks(0, to)) | ends;
+            } else if bishop_attacks(0, from) & generate_from_index(to) != 0 {
+                vals[from as usize][to as usize] =
+                    (bishop_attacks(0, from) & bishop_attacks(0, to)) | ends;
+            }
+        }
+    }
+    vals
+}
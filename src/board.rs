@@ -1,6 +1,6 @@
 use crate::action::StateData;
 use crate::bit_operations::generate_from_index;
-use std::u8;
+use crate::eval::Score;
 extern crate lazy_static;
 use lazy_static::lazy_static;
 
@@ -114,33 +114,99 @@ pub mod pieces {
 *   board-information, used to undo moves.
    ======================================   */
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BoardData {
     pub to_move: bool, // true means white, false means black
     pub bitboards: [u64; 14],
     pub mailbox: [u8; 64],
     pub passant_square: Option<u8>,
     pub castle_rights_mask: u8,
-    pub half_move_counter: u8, // is reset when a pawn moves or a capture takes place
+    pub half_move_counter: u16, // is reset when a pawn moves or a capture takes place
+    pub fullmove_number: u16, // starts at 1, incremented after black moves
     pub prev_states: Vec<StateData>,
+    pub key_history: Vec<u64>, // zobrist key before each move in prev_states
     pub zobrist_key: u64,
+    pub pawn_key: u64, // zobrist key of the pawns alone
+    pub psq_score: Score, // material and piece-square sums, from white's point of view
+    pub phase: i32,       // sum of the phase weights of every piece on the board
 }
 
 impl BoardData {
+    // a board from its bitboards and game state. everything that follows from
+    // the pieces, the mailbox, the keys and the evaluation sums, is worked out here
+    pub fn new(
+        to_move: bool,
+        bitboards: [u64; 14],
+        passant_square: Option<u8>,
+        castle_rights_mask: u8,
+        half_move_counter: u16,
+        fullmove_number: u16,
+    ) -> BoardData {
+        let mut board = BoardData {
+            to_move,
+            bitboards,
+            mailbox: [pieces::NOPIECE; 64],
+            passant_square,
+            castle_rights_mask,
+            half_move_counter,
+            fullmove_number,
+            prev_states: Vec::new(),
+            key_history: Vec::new(),
+            zobrist_key: 0,
+            pawn_key: 0,
+            psq_score: Score::default(),
+            phase: 0,
+        };
+        board.set_mailbox();
+        board.generate_zobristkey();
+        board.generate_eval_sums();
+        board
+    }
+
     pub fn generate_zobristkey(&mut self) {
+        self.zobrist_key = self.compute_zobristkey();
+        self.pawn_key = self.compute_pawn_key();
+    }
+
+    // the pawn key calculated from scratch
+    pub fn compute_pawn_key(&self) -> u64 {
         let mut key = 0;
         for (square, piece) in self.mailbox.iter().enumerate() {
-            if *piece != pieces::NOPIECE {
+            if *piece == pieces::WPAWN || *piece == pieces::BPAWN {
                 key ^= ZOBRIST_TABLES.piecesquares[square][*piece as usize];
             }
         }
+        key
+    }
+
+    // the running evaluation sums, which make_move keeps up to date afterwards
+    pub fn generate_eval_sums(&mut self) {
+        self.psq_score = self.compute_psq_score();
+        self.phase = self.compute_phase();
+    }
 
+    // the passant square's part of the zobrist key. a square the side to move
+    // can't capture on doesn't change which moves follow, so it isn't hashed
+    pub fn passant_key(&self) -> u64 {
         match self.passant_square {
-            None => (),
-            Some(sqr) => {
-                key ^= ZOBRIST_TABLES.passant_square[(sqr & 7) /* modulo of the key gets the file */ as usize];
+            // modulo of the square gets the file
+            Some(sqr) if self.can_capture_passant() => {
+                ZOBRIST_TABLES.passant_square[(sqr & 7) as usize]
             }
+            _ => 0,
         }
+    }
+
+    // the zobrist key calculated from scratch
+    pub fn compute_zobristkey(&self) -> u64 {
+        let mut key = 0;
+        for (square, piece) in self.mailbox.iter().enumerate() {
+            if *piece != pieces::NOPIECE {
+                key ^= ZOBRIST_TABLES.piecesquares[square][*piece as usize];
+            }
+        }
+
+        key ^= self.passant_key();
 
         key ^= ZOBRIST_TABLES.castling_rights[self.castle_rights_mask as usize];
 
@@ -150,7 +216,82 @@ impl BoardData {
             0
         };
 
-        self.zobrist_key = key;
+        key
+    }
+
+    /* ========================================
+    *   assert_consistent checks that all of
+    |   the redundant board state agrees: the
+    *   mailbox and the bitboards, the color
+    |   occupancies, and the incremental
+    *   zobrist key. It only does anything in
+    |   debug builds.
+       ======================================   */
+
+    pub fn assert_consistent(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let mut seen = 0u64;
+        for piece in 0..12 {
+            debug_assert_eq!(
+                seen & self.bitboards[piece],
+                0,
+                "bitboard {} overlaps another piece\n{}",
+                piece,
+                self.to_fen()
+            );
+            seen |= self.bitboards[piece];
+        }
+
+        let white = self.bitboards[0..6].iter().fold(0, |a, b| a | b);
+        let black = self.bitboards[6..12].iter().fold(0, |a, b| a | b);
+        debug_assert_eq!(
+            self.bitboards[pieces::ALLWHITE as usize],
+            white,
+            "white occupancy is out of sync"
+        );
+        debug_assert_eq!(
+            self.bitboards[pieces::ALLBLACK as usize],
+            black,
+            "black occupancy is out of sync"
+        );
+
+        for square in 0..64 {
+            debug_assert_eq!(
+                self.mailbox[square],
+                self.which_piece(square),
+                "mailbox does not match bitboards at square {}",
+                square
+            );
+        }
+
+        debug_assert_eq!(
+            self.zobrist_key,
+            self.compute_zobristkey(),
+            "incremental zobrist key is out of sync"
+        );
+        debug_assert_eq!(
+            self.pawn_key,
+            self.compute_pawn_key(),
+            "incremental pawn key is out of sync"
+        );
+        debug_assert_eq!(
+            self.psq_score,
+            self.compute_psq_score(),
+            "incremental piece-square score is out of sync"
+        );
+        debug_assert_eq!(
+            self.phase,
+            self.compute_phase(),
+            "incremental game phase is out of sync"
+        );
+        debug_assert_eq!(
+            self.key_history.len(),
+            self.prev_states.len(),
+            "key history and state stack have different lengths"
+        );
     }
 
     /* ========================================
@@ -245,3 +386,92 @@ unsafe fn xorshift_u32() -> u32 {
     SEED = num;
     num
 }
+
+#[cfg(test)]
+mod tests {
+    use super::pieces;
+    use crate::action::{actions, Move};
+    use crate::fen::from_fen;
+    use crate::testing::{next_random, PERFT_POSITIONS};
+
+    // plays random games, checking the board after every make and every undo
+    #[test]
+    fn random_games_stay_consistent() {
+        let mut seed = 0x2545f4914f6cdd1d;
+        for game in 0..200 {
+            let mut board = from_fen(PERFT_POSITIONS[game % PERFT_POSITIONS.len()]).unwrap();
+            board.assert_consistent();
+            let mut played = Vec::new();
+            for _ in 0..100 {
+                let mut moves = Vec::new();
+                board.generate_legal_moves(&mut moves);
+                if moves.is_empty() {
+                    break;
+                }
+
+                // make and undo every move, then play a random one
+                for action in &moves {
+                    board.make_move(*action);
+                    board.assert_consistent();
+                    let fen = board.to_fen();
+                    board.undo_move(*action);
+                    board.assert_consistent();
+
+                    // the capture path has to end up in the same place as make_move
+                    if action.move_type() == actions::NORMAL
+                        && board.mailbox[action.move_to() as usize] != pieces::NOPIECE
+                    {
+                        board.do_capture(*action);
+                        board.assert_consistent();
+                        assert_eq!(board.to_fen(), fen);
+                        board.undo_capture(*action);
+                        board.assert_consistent();
+                    }
+                }
+
+                // every capture and promotion, and nothing else
+                let mut captures = Vec::new();
+                board.generate_legal_captures(&mut captures);
+                let expected: Vec<_> = moves
+                    .iter()
+                    .filter(|action| {
+                        action.move_type() == actions::PROMOTION
+                            || action.move_type() == actions::PASSANT
+                            || board.mailbox[action.move_to() as usize] != pieces::NOPIECE
+                    })
+                    .collect();
+                assert_eq!(captures.len(), expected.len());
+                assert!(expected.iter().all(|action| captures.contains(action)));
+                let action = moves[(next_random(&mut seed) % moves.len() as u64) as usize];
+                board.make_move(action);
+                board.assert_consistent();
+                played.push(action);
+            }
+
+            while let Some(action) = played.pop() {
+                board.undo_move(action);
+                board.assert_consistent();
+            }
+            assert_eq!(board.to_fen(), PERFT_POSITIONS[game % PERFT_POSITIONS.len()]);
+        }
+    }
+
+    #[test]
+    fn null_move_is_undone_exactly() {
+        let mut board =
+            from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        let fen = board.to_fen();
+        let key = board.zobrist_key;
+
+        board.make_null_move();
+        board.assert_consistent();
+        assert!(!board.to_move);
+        assert_eq!(board.passant_square, None);
+        assert_ne!(board.zobrist_key, key);
+
+        board.undo_null_move();
+        board.assert_consistent();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.zobrist_key, key);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::board::BoardData;
+
+/* ========================================
+*   Draws by the move history. A position
+|   can only repeat since the last capture
+*   or pawn move, so the scan back through
+|   key_history stops at the half move
+*   counter. Checkmate on the move that
+|   completes a rule still wins, which is
+*   left to the caller to check.
+   ======================================   */
+
+impl BoardData {
+    // how many times this position came up before, with the same side to move
+    pub fn repetitions(&self) -> usize {
+        self.repetitions_within(self.key_history.len())
+    }
+
+    // only looks back over the last plies positions
+    fn repetitions_within(&self, plies: usize) -> usize {
+        let history = &self.key_history;
+        let reversible = (self.half_move_counter as usize)
+            .min(history.len())
+            .min(plies);
+        // the position two plies back has the other side's last move undone
+        // and differs in the piece that moved, so four is the nearest repeat
+        (4..=reversible)
+            .step_by(2)
+            .filter(|back| history[history.len() - back] == self.zobrist_key)
+            .count()
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetitions() >= 2
+    }
+
+    // for the search, which treats any repeat inside its own tree as a draw:
+    // the side that could avoid the repetition would have if it were better.
+    // positions from before the root still need to occur three times
+    pub fn is_search_repetition(&self, plies_from_root: usize) -> bool {
+        self.repetitions_within(plies_from_root) >= 1 || self.is_threefold_repetition()
+    }
+
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.half_move_counter >= 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::BoardData;
+    use crate::fen::{from_fen, startpos};
+
+    fn play(board: &mut BoardData, moves: &[&str]) {
+        for move_str in moves {
+            let action = board.parse_uci_move(move_str).unwrap();
+            board.make_move(action);
+        }
+    }
+
+    const SHUFFLE: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    #[test]
+    fn counts_repetitions() {
+        let mut board = startpos();
+        assert_eq!(board.repetitions(), 0);
+        play(&mut board, &SHUFFLE);
+        assert_eq!(board.repetitions(), 1);
+        assert!(!board.is_threefold_repetition());
+
+        play(&mut board, &SHUFFLE[..3]);
+        let last = board.parse_uci_move(SHUFFLE[3]).unwrap();
+        board.make_move(last);
+        assert!(board.is_threefold_repetition());
+        board.undo_move(last);
+        assert!(!board.is_threefold_repetition());
+    }
+
+    #[test]
+    fn pawn_move_resets_repetitions() {
+        let mut board = startpos();
+        play(&mut board, &SHUFFLE);
+        play(&mut board, &["e2e3", "e7e6"]);
+        play(&mut board, &SHUFFLE);
+        assert_eq!(board.repetitions(), 1);
+    }
+
+    // the first occurrence follows a double push that can't be taken en passant
+    #[test]
+    fn repetition_after_a_double_push() {
+        let mut board = startpos();
+        play(&mut board, &["e2e4", "g8f6", "g1f3", "f6g8", "f3g1"]);
+        assert_eq!(board.repetitions(), 1);
+        play(&mut board, &["g8f6", "g1f3", "f6g8", "f3g1"]);
+        assert!(board.is_threefold_repetition());
+    }
+
+    // a passant square that can be used is part of the position
+    #[test]
+    fn usable_passant_square_is_not_a_repetition() {
+        let mut board = from_fen("4k3/8/8/8/5p2/8/4P3/4K2N w - - 0 1").unwrap();
+        play(&mut board, &["e2e4", "e8d8", "h1g3", "d8e8", "g3h1"]);
+        assert_eq!(board.repetitions(), 0);
+    }
+
+    #[test]
+    fn search_repetition_needs_twofold_only_inside_the_tree() {
+        let mut board = startpos();
+        play(&mut board, &SHUFFLE);
+        // the repeat happened before the root
+        assert!(!board.is_search_repetition(0));
+        // the repeat happened inside the search
+        assert!(board.is_search_repetition(4));
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        assert!(!from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 99 80")
+            .unwrap()
+            .is_fifty_move_draw());
+        assert!(from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 100 80")
+            .unwrap()
+            .is_fifty_move_draw());
+    }
+}
@@ -0,0 +1,421 @@
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+use crate::bit_operations::pop_ls1b;
+use crate::board::{pieces, BoardData};
+use crate::pawns::PawnTable;
+use lazy_static::lazy_static;
+
+/* ========================================
+*   Static evaluation. Every term is scored
+|   twice, once for the middlegame and once
+*   for the endgame, and the two are blended
+|   by how much material is left. Scores are
+*   in centipawns, positive when the side to
+|   move is better.
+   ======================================   */
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Score {
+        Score { mg, eg }
+    }
+
+    // phase runs from MAX_PHASE with all pieces on the board down to 0
+    #[inline]
+    pub fn taper(self, phase: i32) -> i32 {
+        let phase = phase.min(MAX_PHASE);
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+// values and tables are PeSTO's, as a starting point for tuning.
+// indexed by piece type: king, queen, bishop, knight, rook, pawn
+const MG_VALUES: [i32; 6] = [0, 1025, 365, 337, 477, 82];
+const EG_VALUES: [i32; 6] = [0, 936, 297, 281, 512, 94];
+
+// how much each piece type counts towards the game phase
+pub const PHASE_WEIGHTS: [i32; 6] = [0, 4, 1, 1, 2, 0];
+pub const MAX_PHASE: i32 = 24;
+
+/* ========================================
+*   Piece-square tables, from white's point
+|   of view and written the way a board is
+*   drawn: the first row is the eighth rank.
+|   A white piece on square sq reads index
+*   sq ^ 56, a black piece reads index sq.
+   ======================================   */
+
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MG_KNIGHT: [i32; 64] = [
+    -167, -89, -34, -49,  61, -97, -15, -107,
+     -73, -41,  72,  36,  23,  62,   7,  -17,
+     -47,  60,  37,  65,  84, 129,  73,   44,
+      -9,  17,  19,  53,  37,  69,  18,   22,
+     -13,   4,  16,  13,  28,  19,  21,   -8,
+     -23,  -9,  12,  10,  19,  17,  25,  -16,
+     -29, -53, -12,  -3,  -1,  18, -14,  -19,
+    -105, -21, -58, -33, -17, -28, -19,  -23,
+];
+
+#[rustfmt::skip]
+const EG_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MG_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const EG_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+
+#[rustfmt::skip]
+const EG_ROOK: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,
+];
+
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MG_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const EG_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+// by piece type, in the same order as MG_VALUES
+const MG_TABLES: [&[i32; 64]; 6] = [
+    &MG_KING, &MG_QUEEN, &MG_BISHOP, &MG_KNIGHT, &MG_ROOK, &MG_PAWN,
+];
+const EG_TABLES: [&[i32; 64]; 6] = [
+    &EG_KING, &EG_QUEEN, &EG_BISHOP, &EG_KNIGHT, &EG_ROOK, &EG_PAWN,
+];
+
+lazy_static! {
+    // material and placement together, positive for white and negative for black
+    pub static ref PIECE_SQUARE_TABLES: Box<[[Score; 64]; 12]> =
+        Box::new(generate_piece_square_tables());
+}
+
+fn generate_piece_square_tables() -> [[Score; 64]; 12] {
+    let mut tables = [[Score::default(); 64]; 12];
+    for piece_type in 0..6 {
+        for sqr in 0..64 {
+            let white = Score::new(
+                MG_VALUES[piece_type] + MG_TABLES[piece_type][sqr ^ 56],
+                EG_VALUES[piece_type] + EG_TABLES[piece_type][sqr ^ 56],
+            );
+            let black = Score::new(
+                MG_VALUES[piece_type] + MG_TABLES[piece_type][sqr],
+                EG_VALUES[piece_type] + EG_TABLES[piece_type][sqr],
+            );
+            tables[piece_type][sqr] = white;
+            tables[piece_type + 6][sqr] = -black;
+        }
+    }
+    tables
+}
+
+impl BoardData {
+    // material and piece-square terms are the sums make_move keeps, pawn
+    // structure comes from the pawn hash table when it can. only mobility
+    // and king attacks walk the pieces
+    pub fn evaluate(&self, pawns: &mut PawnTable) -> i32 {
+        let score = (self.psq_score + self.pawn_score(pawns) + self.activity_score())
+            .taper(self.game_phase());
+        if self.to_move {
+            score
+        } else {
+            -score
+        }
+    }
+
+    // MAX_PHASE in the opening, 0 with only kings and pawns left
+    #[inline]
+    pub fn game_phase(&self) -> i32 {
+        self.phase.min(MAX_PHASE)
+    }
+
+    // material and piece-square terms from scratch, from white's point of view
+    pub fn compute_psq_score(&self) -> Score {
+        let mut score = Score::default();
+        for piece in pieces::WKING..=pieces::BPAWN {
+            let mut board = self.bitboards[piece as usize];
+            while board != 0 {
+                let sqr = pop_ls1b(&mut board);
+                score += PIECE_SQUARE_TABLES[piece as usize][sqr as usize];
+            }
+        }
+        score
+    }
+
+    // the phase from scratch, before it is capped at MAX_PHASE
+    pub fn compute_phase(&self) -> i32 {
+        let signature = self.material_signature();
+        (pieces::WKING..=pieces::BPAWN)
+            .map(|piece| PHASE_WEIGHTS[(piece % 6) as usize] * signature.count(piece) as i32)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_PHASE;
+    use crate::action::{actions, Move};
+    use crate::fen::{from_fen, startpos};
+    use crate::pawns::PawnTable;
+
+    #[test]
+    fn startpos_is_level() {
+        let board = startpos();
+        assert_eq!(board.evaluate(&mut PawnTable::new()), 0);
+        assert_eq!(board.game_phase(), MAX_PHASE);
+    }
+
+    #[test]
+    fn mirrored_positions_score_the_same() {
+        let pairs = [
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            ),
+            (
+                "8/8/4k3/4r3/8/3RK3/4P3/8 w - - 0 1",
+                "8/4p3/3rk3/8/4R3/4K3/8/8 b - - 0 1",
+            ),
+        ];
+        for (fen, mirrored) in pairs {
+            assert_eq!(
+                from_fen(fen).unwrap().evaluate(&mut PawnTable::new()),
+                from_fen(mirrored).unwrap().evaluate(&mut PawnTable::new())
+            );
+        }
+    }
+
+    #[test]
+    fn extra_material_is_better() {
+        // white is a queen up, scored from either side
+        let white = from_fen("3qk3/8/8/8/8/8/8/3QKQ2 w - - 0 1").unwrap();
+        let black = from_fen("3qk3/8/8/8/8/8/8/3QKQ2 b - - 0 1").unwrap();
+        assert!(white.evaluate(&mut PawnTable::new()) > 500);
+        assert_eq!(
+            black.evaluate(&mut PawnTable::new()),
+            -white.evaluate(&mut PawnTable::new())
+        );
+    }
+
+    #[test]
+    fn phase_falls_with_material() {
+        assert_eq!(
+            from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1")
+                .unwrap()
+                .game_phase(),
+            0
+        );
+        assert_eq!(
+            from_fen("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1")
+                .unwrap()
+                .game_phase(),
+            8
+        );
+    }
+
+    fn assert_sums_fresh(board: &crate::board::BoardData, context: &str) {
+        assert_eq!(board.psq_score, board.compute_psq_score(), "{}", context);
+        assert_eq!(board.phase, board.compute_phase(), "{}", context);
+    }
+
+    #[test]
+    fn incremental_sums_follow_make_and_undo() {
+        // castling both ways, en passant, and promotions with and without a capture
+        let positions = [
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", ["e1g1", "e8c8"]),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", ["e1c1", "e8g8"]),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", ["e5d6", "e8d8"]),
+            ("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1", ["d4e3", "e1e2"]),
+            ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", ["a7b8n", "e8e7"]),
+            ("4k3/8/8/8/8/8/p7/1R2K3 b - - 0 1", ["a2a1q", "e1e2"]),
+        ];
+        for (fen, moves) in positions {
+            let mut board = from_fen(fen).unwrap();
+            let mut played = Vec::new();
+            for move_str in moves {
+                let action = board.parse_uci_move(move_str).unwrap();
+                board.make_move(action);
+                assert_sums_fresh(&board, move_str);
+                played.push(action);
+            }
+            while let Some(action) = played.pop() {
+                board.undo_move(action);
+                assert_sums_fresh(&board, fen);
+            }
+            assert_eq!(board.psq_score, from_fen(fen).unwrap().psq_score);
+        }
+
+        // and every legal move of a busy position, through both make paths
+        let mut board =
+            from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        let mut moves = Vec::new();
+        board.generate_legal_moves(&mut moves);
+        for action in moves {
+            board.make_move(action);
+            assert_sums_fresh(&board, &action.to_uci());
+            board.undo_move(action);
+            assert_sums_fresh(&board, &action.to_uci());
+        }
+        let mut captures = Vec::new();
+        board.generate_legal_captures(&mut captures);
+        for action in captures {
+            if action.move_type() == actions::NORMAL {
+                board.do_capture(action);
+                assert_sums_fresh(&board, &action.to_uci());
+                board.undo_capture(action);
+                assert_sums_fresh(&board, &action.to_uci());
+            }
+        }
+    }
+}
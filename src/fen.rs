@@ -1,7 +1,9 @@
 use core::panic;
+use std::fmt;
 
 use crate::bit_operations;
 use crate::board::{BoardData};
+use crate::validate::PositionError;
 
 impl BoardData {
     pub fn to_fen(&self) -> String {
@@ -48,6 +50,9 @@ impl BoardData {
             
         }
         // since it never gets to 64 it will never get pushed
+        if counter != 0 {
+            so_far_string += &counter.to_string();
+        }
         fen_list.push(so_far_string);
        
         let mut val = fen_list.iter().rev().fold(fen_list[0].clone(), |a, b| a + "/" + b);
@@ -82,12 +87,15 @@ impl BoardData {
             _ => panic!("Invalid castle!"),
         };
 
-        let move_counter = self.half_move_counter / 2;
-
-        format!("{} {} {} {} {} {}", val, tomove, castlemask, passant, move_counter, self.half_move_counter)
+        format!(
+            "{} {} {} {} {} {}",
+            val, tomove, castlemask, passant, self.half_move_counter, self.fullmove_number
+        )
     }
 
     //prints out a board for debugging
+    // is_multiple_of would need rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
     pub fn to_boardstring(&self) -> String {
         let mailbox = self.mailbox;
         let mut to_return_str = String::new();
@@ -120,30 +128,163 @@ impl BoardData {
             + &format!("{castle:4b}")
             + "\nHalf Move Counter: "
             + &self.half_move_counter.to_string()
+            + "\nFullmove Number: "
+            + &self.fullmove_number.to_string()
             + "\nPassant Square: "
             + &passantval.to_string()
     }
 }
 
-pub fn from_fen(fen_string: &str) -> BoardData {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    TooManyFields(usize),
+    InvalidPiece(char),
+    WrongRankCount(usize),
+    WrongFileCount { rank: u8, files: u32 },
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    InvalidPosition(PositionError),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "missing {} field", field),
+            FenError::TooManyFields(count) => write!(f, "expected at most 6 fields, found {}", count),
+            FenError::InvalidPiece(symbol) => write!(f, "invalid piece '{}'", symbol),
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::WrongFileCount { rank, files } => {
+                write!(f, "expected 8 files on rank {}, found {}", rank, files)
+            }
+            FenError::InvalidSideToMove(field) => write!(f, "invalid side to move '{}'", field),
+            FenError::InvalidCastling(field) => write!(f, "invalid castling rights '{}'", field),
+            FenError::InvalidPassant(field) => write!(f, "invalid en passant square '{}'", field),
+            FenError::InvalidHalfmoveClock(field) => write!(f, "invalid halfmove clock '{}'", field),
+            FenError::InvalidFullmoveNumber(field) => {
+                write!(f, "invalid fullmove number '{}'", field)
+            }
+            FenError::InvalidPosition(error) => write!(f, "invalid position: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+pub const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub fn startpos() -> BoardData {
+    from_fen(STARTPOS).expect("the starting position is a valid fen")
+}
+
+// also accepts four-field EPD style positions, without the clocks.
+// positions that fail BoardData::validate are rejected
+pub fn from_fen(fen_string: &str) -> Result<BoardData, FenError> {
+    let fen_board: Vec<&str> = fen_string.split_whitespace().collect();
+    if fen_board.len() > 6 {
+        return Err(FenError::TooManyFields(fen_board.len()));
+    }
+    let field = |idx: usize, name: &'static str| {
+        fen_board.get(idx).copied().ok_or(FenError::MissingField(name))
+    };
+
+    let bit_boards = parse_board(field(0, "board")?)?;
+
+    let to_move = match field(1, "side to move")? {
+        "w" => true,
+        "b" => false,
+        other => return Err(FenError::InvalidSideToMove(other.to_string())),
+    };
+
+    let castling = field(2, "castling")?;
+    let mut base_mask: u8 = 0;
+    if castling != "-" {
+        for symbol in castling.chars() {
+            let right = match symbol {
+                'K' => crate::board::WK,
+                'Q' => crate::board::WQ,
+                'k' => crate::board::BK,
+                'q' => crate::board::BQ,
+                _ => return Err(FenError::InvalidCastling(castling.to_string())),
+            };
+            if base_mask & right != 0 {
+                return Err(FenError::InvalidCastling(castling.to_string()));
+            }
+            base_mask |= right;
+        }
+    }
+
+    let passant = field(3, "en passant")?;
+    let passant_square = if passant == "-" {
+        None
+    } else {
+        match sqr_to_index(passant) {
+            // a double pushed pawn always leaves its passant square on the third or sixth rank
+            Some(sqr) if sqr >> 3 == 2 || sqr >> 3 == 5 => Some(sqr),
+            _ => return Err(FenError::InvalidPassant(passant.to_string())),
+        }
+    };
+
+    let half_move_ctr = match fen_board.get(4) {
+        None => 0,
+        Some(clock) => clock
+            .parse()
+            .map_err(|_| FenError::InvalidHalfmoveClock(clock.to_string()))?,
+    };
+
+    let fullmove_number = match fen_board.get(5) {
+        None => 1,
+        Some(number) => match number.parse::<u16>() {
+            Ok(val) if val > 0 => val,
+            _ => return Err(FenError::InvalidFullmoveNumber(number.to_string())),
+        },
+    };
+
+    let new_board = BoardData::new(
+        to_move,
+        bit_boards,
+        passant_square,
+        base_mask,
+        half_move_ctr,
+        fullmove_number,
+    );
+    new_board.validate().map_err(FenError::InvalidPosition)?;
+    Ok(new_board)
+}
+
+fn parse_board(board: &str) -> Result<[u64; 14], FenError> {
     let mut bit_boards: [u64; 14] = [0; 14];
-    let fen_board: Vec<&str> = fen_string.split(' ').collect();
-    let board = fen_board[0];
-    let mut file = 0;
-    let mut rank = 7;
-    for symbol in board.chars() {
-        if symbol == '/' {
-            file = 0;
-            rank -= 1;
-        } else if symbol.is_numeric() {
-            let val = char::to_digit(symbol, 10).unwrap();
-            file += val;
-        } else {
-            let index: u8 = (rank as u8 * 8) + file as u8;
-            let val = bit_operations::generate_from_index(index);
-            bit_boards[piece_val_from_symbol(symbol) as usize] |=
-                bit_operations::generate_from_index(index);
-            file += 1;
+    let ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+
+    for (rank_idx, rank_str) in ranks.iter().enumerate() {
+        let rank = 7 - rank_idx as u8;
+        let mut file: u32 = 0;
+        for symbol in rank_str.chars() {
+            if let Some(val) = symbol.to_digit(10) {
+                if val == 0 || val > 8 {
+                    return Err(FenError::InvalidPiece(symbol));
+                }
+                file += val;
+            } else {
+                let piece = piece_val_from_symbol(symbol).ok_or(FenError::InvalidPiece(symbol))?;
+                if file < 8 {
+                    let index: u8 = (rank * 8) + file as u8;
+                    bit_boards[piece as usize] |= bit_operations::generate_from_index(index);
+                }
+                file += 1;
+            }
+        }
+        if file != 8 {
+            return Err(FenError::WrongFileCount {
+                rank: rank + 1,
+                files: file,
+            });
         }
     }
 
@@ -159,57 +300,25 @@ pub fn from_fen(fen_string: &str) -> BoardData {
 
     bit_boards[12] = base_white;
     bit_boards[13] = base_black;
-
-    let to_move = fen_board[1] == "w";
-
-    let mut base_mask: u8 = 0;
-    if fen_board[2].contains('K') {
-        base_mask |= crate::board::WK;
-    }
-    if fen_board[2].contains('Q') {
-        base_mask |= crate::board::WQ;
-    }
-    if fen_board[2].contains('k') {
-        base_mask |= crate::board::BK;
-    }
-    if fen_board[2].contains('q') {
-        base_mask |= crate::board::BQ;
-    }
-
-    let half_move_ctr = fen_board[5].parse().unwrap();
-
-    let mut new_board = BoardData {
-        to_move,
-        bitboards: bit_boards,
-        mailbox: [0; 64],
-        passant_square: sqr_to_index(fen_board[3]),
-        castle_rights_mask: base_mask,
-        half_move_counter: half_move_ctr,
-        zobrist_key: 0, // do this later
-        prev_states: Vec::new(),
-    };
-
-    new_board.set_mailbox();
-    new_board.generate_zobristkey();
-    new_board
+    Ok(bit_boards)
 }
 
-fn piece_val_from_symbol(val: char) -> u8 {
+fn piece_val_from_symbol(val: char) -> Option<u8> {
     match val {
-        'K' => 0,
-        'Q' => 1,
-        'B' => 2,
-        'N' => 3,
-        'R' => 4,
-        'P' => 5,
-
-        'k' => 6,
-        'q' => 7,
-        'b' => 8,
-        'n' => 9,
-        'r' => 10,
-        'p' => 11,
-        _ => panic!("Invalid FEN!"),
+        'K' => Some(0),
+        'Q' => Some(1),
+        'B' => Some(2),
+        'N' => Some(3),
+        'R' => Some(4),
+        'P' => Some(5),
+
+        'k' => Some(6),
+        'q' => Some(7),
+        'b' => Some(8),
+        'n' => Some(9),
+        'r' => Some(10),
+        'p' => Some(11),
+        _ => None,
     }
 }
 
@@ -234,6 +343,9 @@ fn match_pval_to_char(pval: u8) -> char {
 
 fn sqr_to_index(square: &str) -> Option<u8> {
     let coords: Vec<char> = square.chars().collect();
+    if coords.len() != 2 {
+        return None;
+    }
     let file = match coords[0] {
         'a' => 0,
         'b' => 1,
@@ -246,11 +358,14 @@ fn sqr_to_index(square: &str) -> Option<u8> {
         _ => return None,
     };
 
-    let rank = char::to_digit(coords[1], 10).unwrap() - 1;
+    let rank = match char::to_digit(coords[1], 10) {
+        Some(val @ 1..=8) => val - 1,
+        _ => return None,
+    };
     Some((rank * 8 + file) as u8)
 }
 
-fn idx_to_coordsquare(idx: u8) -> String {
+pub fn idx_to_coordsquare(idx: u8) -> String {
     let rank = (idx >> 3) + 1;
     let file = match idx & 7{
         0 => "a",
@@ -265,4 +380,159 @@ fn idx_to_coordsquare(idx: u8) -> String {
     };
 
    format!("{}{}", file, rank)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{next_random, PERFT_POSITIONS, POSITION_5};
+
+    // the perft positions, plus a passant square and clocks far from their start
+    fn positions() -> Vec<&'static str> {
+        let mut positions = PERFT_POSITIONS.to_vec();
+        positions.push("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        positions.push("8/8/4k3/8/2p5/8/B2K4/8 b - - 37 112");
+        positions
+    }
+
+    fn assert_round_trip(fen: &str) {
+        let board = from_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    #[test]
+    fn known_positions_round_trip() {
+        for fen in positions() {
+            assert_round_trip(fen);
+        }
+    }
+
+    #[test]
+    fn epd_gets_default_clocks() {
+        let board = from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        assert_eq!(board.to_fen(), STARTPOS);
+    }
+
+    #[test]
+    fn random_games_round_trip() {
+        let mut seed = 0x9e3779b97f4a7c15;
+        let mut checked = 0;
+        let positions = positions();
+        for game in 0..100 {
+            let mut board = from_fen(positions[game % positions.len()]).unwrap();
+            for _ in 0..60 {
+                let mut moves = Vec::new();
+                board.generate_legal_moves(&mut moves);
+                if moves.is_empty() {
+                    break;
+                }
+                let action = moves[(next_random(&mut seed) % moves.len() as u64) as usize];
+                board.make_move(action);
+
+                let fen = board.to_fen();
+                let parsed = from_fen(&fen).unwrap();
+                assert_eq!(parsed.to_fen(), fen);
+                assert_eq!(parsed.bitboards, board.bitboards);
+                assert_eq!(parsed.fullmove_number, board.fullmove_number);
+                checked += 1;
+            }
+        }
+        assert!(checked > 1000);
+    }
+
+    #[test]
+    fn undo_restores_clocks() {
+        let mut board = from_fen(POSITION_5).unwrap();
+        let mut moves = Vec::new();
+        board.generate_legal_moves(&mut moves);
+        for action in moves {
+            board.make_move(action);
+            board.undo_move(action);
+            assert_eq!(board.to_fen(), POSITION_5);
+        }
+    }
+
+    #[test]
+    fn fullmove_number_advances_after_black_moves() {
+        let mut board = startpos();
+        let e4 = board.parse_uci_move("e2e4").unwrap();
+        board.make_move(e4);
+        assert_eq!(board.fullmove_number, 1);
+        let e5 = board.parse_uci_move("e7e5").unwrap();
+        board.make_move(e5);
+        assert_eq!(board.fullmove_number, 2);
+        // no white pawn can take on e6, so the passant square isn't kept
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+        );
+    }
+
+    #[test]
+    fn fen_errors() {
+        let board = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+        let with_board = |rest: &str| format!("{} {}", board, rest);
+        let string = |text: &str| text.to_string();
+        let cases = [
+            (String::new(), FenError::MissingField("board")),
+            (String::from(board), FenError::MissingField("side to move")),
+            (with_board("w KQkq"), FenError::MissingField("en passant")),
+            (with_board("w KQkq - 0 1 x"), FenError::TooManyFields(7)),
+            (
+                "rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+                FenError::InvalidPiece('x'),
+            ),
+            (
+                "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+                FenError::InvalidPiece('9'),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+                FenError::WrongRankCount(7),
+            ),
+            (
+                "rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+                FenError::WrongFileCount { rank: 7, files: 7 },
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1".to_string(),
+                FenError::WrongFileCount { rank: 1, files: 9 },
+            ),
+            (with_board("x KQkq - 0 1"), FenError::InvalidSideToMove(string("x"))),
+            (with_board("w KQkx - 0 1"), FenError::InvalidCastling(string("KQkx"))),
+            (with_board("w KKq - 0 1"), FenError::InvalidCastling(string("KKq"))),
+            (with_board("w KQkq e4 0 1"), FenError::InvalidPassant(string("e4"))),
+            (with_board("w KQkq z6 0 1"), FenError::InvalidPassant(string("z6"))),
+            (with_board("w KQkq - -1 1"), FenError::InvalidHalfmoveClock(string("-1"))),
+            (
+                with_board("w KQkq - 70000 1"),
+                FenError::InvalidHalfmoveClock(string("70000")),
+            ),
+            (with_board("w KQkq - 0 0"), FenError::InvalidFullmoveNumber(string("0"))),
+            (with_board("w KQkq - 0 x"), FenError::InvalidFullmoveNumber(string("x"))),
+            (
+                "8/8/4k3/8/8/8/8/8 w - - 0 1".to_string(),
+                FenError::InvalidPosition(PositionError::WrongKingCount { white: 0, black: 1 }),
+            ),
+        ];
+        for (fen, error) in cases {
+            assert_eq!(from_fen(&fen).map(|_| ()), Err(error), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn clocks_stop_at_their_maximum() {
+        // the halfmove clock used to wrap past 255 and lose the fifty move draw
+        let mut board = from_fen("4k3/8/8/8/8/8/8/4K2R w K - 254 1").unwrap();
+        for move_str in ["h1h2", "e8d8"] {
+            board.make_move(board.parse_uci_move(move_str).unwrap());
+        }
+        assert_eq!(board.half_move_counter, 256);
+        assert!(board.is_fifty_move_draw());
+
+        let mut board = from_fen("4k3/8/8/8/8/8/8/4K2R b K - 65535 65535").unwrap();
+        board.make_move(board.parse_uci_move("e8d8").unwrap());
+        assert_eq!(board.half_move_counter, u16::MAX);
+        assert_eq!(board.fullmove_number, u16::MAX);
+        assert_round_trip(&board.to_fen());
+    }
+}
@@ -5,30 +5,168 @@ mod bit_operations;
 mod board;
 mod fen;
 mod bitboard_gen; // generating bitboards
+mod attacks;
 mod movegen;
 mod action;
-use action::actions;
-use action::Action;
+mod perft;
+mod notation;
+mod pgn;
+mod validate;
+mod draw;
+mod status;
+mod material;
+mod eval;
+mod pawns;
+mod mobility;
+mod search;
+mod tt;
+mod uci;
+#[cfg(test)]
+mod testing;
+use action::Move;
 use std::env;
+use std::fmt;
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "usage: chess [perft [--fen \"<fen>\"] [--depth <n>] [--divide]]";
 
 fn main() {
-    env::set_var("RUST_BACKTRACE", "2");
-    let test: Action = actions::new(36, 45, actions::PASSANT, 0);
-    let mut the_board = fen::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
-    println!("{}", the_board.to_boardstring());
-    println!("zbkey: {}", the_board.zobrist_key);
-
-    
-    the_board.make_move(test);
-    the_board.set_mailbox();
-    println!("{}", the_board.to_boardstring());
-    println!("zbkey: {}", the_board.zobrist_key);
-
-    the_board.undo_move(test);
-
-    the_board.set_mailbox();
-    the_board.generate_zobristkey();
-    println!("{}", the_board.to_boardstring());
-    println!("zbkey: {}", the_board.zobrist_key)
-    
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("perft") => match perft_command(&args[1..]) {
+            Ok(()) => (),
+            Err(CommandError::Usage) => exit_with_usage(),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1)
+            }
+        },
+        None => uci::Uci::new().run(),
+        _ => exit_with_usage(),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1)
+}
+
+#[derive(Debug, PartialEq)]
+enum CommandError {
+    Usage,
+    InvalidFen(fen::FenError),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Usage => write!(f, "{}", USAGE),
+            CommandError::InvalidFen(e) => write!(f, "invalid fen: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct PerftArgs {
+    fen: String,
+    depth: u8,
+    divide: bool,
+}
+
+impl Default for PerftArgs {
+    fn default() -> Self {
+        PerftArgs {
+            fen: String::from(fen::STARTPOS),
+            depth: 5,
+            divide: false,
+        }
+    }
+}
+
+fn parse_perft_args(args: &[String]) -> Result<PerftArgs, CommandError> {
+    let mut parsed = PerftArgs::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fen" => parsed.fen = args.next().ok_or(CommandError::Usage)?.clone(),
+            "--depth" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => parsed.depth = val,
+                _ => return Err(CommandError::Usage),
+            },
+            "--divide" => parsed.divide = true,
+            _ => return Err(CommandError::Usage),
+        }
+    }
+    Ok(parsed)
+}
+
+/* ========================================
+*   Output matches stockfish's "go perft",
+|   one "move: nodes" line per root move
+*   followed by the total, so the two can
+|   be diffed line by line. Timing goes to
+*   stderr to keep stdout diffable.
+   ======================================   */
+fn perft_command(args: &[String]) -> Result<(), CommandError> {
+    let PerftArgs { fen, depth, divide } = parse_perft_args(args)?;
+    let mut board = fen::from_fen(&fen).map_err(CommandError::InvalidFen)?;
+
+    let start = Instant::now();
+    let nodes = if divide && depth > 0 {
+        let divided = board.perft_divide(depth);
+        for (action, nodes) in &divided {
+            println!("{}: {}", action.to_uci(), nodes);
+        }
+        println!();
+        divided.iter().map(|(_, nodes)| nodes).sum()
+    } else {
+        board.perft(depth)
+    };
+    let elapsed = start.elapsed();
+
+    println!("Nodes searched: {}", nodes);
+    eprintln!("Time: {} ms", elapsed.as_millis());
+    eprintln!("NPS: {}", (nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn perft_arguments() {
+        assert_eq!(parse_perft_args(&[]), Ok(PerftArgs::default()));
+        assert_eq!(
+            parse_perft_args(&args("--depth 3 --divide")),
+            Ok(PerftArgs {
+                fen: String::from(fen::STARTPOS),
+                depth: 3,
+                divide: true,
+            })
+        );
+        // the fen is a single argument, as the shell passes a quoted string
+        let mut with_fen = args("--fen");
+        with_fen.push(testing::KIWIPETE.to_string());
+        assert_eq!(parse_perft_args(&with_fen).unwrap().fen, testing::KIWIPETE);
+
+        for bad in ["--depth", "--depth x", "--depth 300", "--fen", "--depht 3", "5"] {
+            assert_eq!(parse_perft_args(&args(bad)), Err(CommandError::Usage), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn perft_rejects_invalid_fen() {
+        let mut bad_fen = args("--depth 1 --fen");
+        bad_fen.push(String::from("8/8/8 w - - 0 1"));
+        assert!(matches!(
+            perft_command(&bad_fen),
+            Err(CommandError::InvalidFen(fen::FenError::WrongRankCount(3)))
+        ));
+    }
 }
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::bit_operations::pop_count;
+use crate::board::{pieces, BoardData};
+
+/* ========================================
+*   A material signature is how many of
+|   each piece is on the board, counted
+*   from the bitboards. It is what draw
+|   detection and endgame scaling look at
+*   instead of the exact squares. Counts
+|   are indexed like the bitboards, so
+*   pieces::WROOK + 6 is the black rooks.
+   ======================================   */
+
+// squares a1, c1, b2... in other words every square where file + rank is even
+const DARK_SQUARES: u64 = 0xaa55aa55aa55aa55;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    counts: [u8; 12],
+}
+
+impl MaterialSignature {
+    #[inline]
+    pub fn count(&self, piece: u8) -> u8 {
+        self.counts[piece as usize]
+    }
+
+    // a piece of the given side, from its white index
+    #[inline]
+    fn side_count(&self, white_piece: u8, white: bool) -> u8 {
+        let offset = if white { 0 } else { 6 };
+        self.counts[(white_piece + offset) as usize]
+    }
+
+    pub fn pawns(&self, white: bool) -> u8 {
+        self.side_count(pieces::WPAWN, white)
+    }
+
+    // knights and bishops
+    pub fn minors(&self, white: bool) -> u8 {
+        self.side_count(pieces::WKNIGHT, white) + self.side_count(pieces::WBISHOP, white)
+    }
+
+    // rooks and queens
+    pub fn majors(&self, white: bool) -> u8 {
+        self.side_count(pieces::WROOK, white) + self.side_count(pieces::WQUEEN, white)
+    }
+
+    // everything but kings
+    pub fn total(&self) -> u8 {
+        self.counts.iter().sum::<u8>() - self.count(pieces::WKING) - self.count(pieces::BKING)
+    }
+
+    // a unique number per signature, four bits per piece type without the kings
+    pub fn key(&self) -> u64 {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(piece, _)| *piece as u8 % 6 != pieces::WKING)
+            .fold(0, |key, (_, count)| key << 4 | (*count).min(15) as u64)
+    }
+}
+
+// the usual endgame naming, white first: "KRPvKR"
+impl fmt::Display for MaterialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order = [
+            (pieces::WKING, 'K'),
+            (pieces::WQUEEN, 'Q'),
+            (pieces::WROOK, 'R'),
+            (pieces::WBISHOP, 'B'),
+            (pieces::WKNIGHT, 'N'),
+            (pieces::WPAWN, 'P'),
+        ];
+        for (i, offset) in [0, 6].into_iter().enumerate() {
+            if i == 1 {
+                write!(f, "v")?;
+            }
+            for (piece, letter) in order {
+                for _ in 0..self.count(piece + offset) {
+                    write!(f, "{}", letter)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BoardData {
+    pub fn material_signature(&self) -> MaterialSignature {
+        let mut counts = [0; 12];
+        for (piece, count) in counts.iter_mut().enumerate() {
+            *count = pop_count(self.bitboards[piece]);
+        }
+        MaterialSignature { counts }
+    }
+
+    // no sequence of legal moves can end in checkmate: K vs K, K+minor vs K,
+    // and any number of bishops that all stand on squares of one colour
+    pub fn is_insufficient_material(&self) -> bool {
+        // a pawn, rook or queen can always mate. search asks at every node, and
+        // one of them is nearly always there, so look before counting anything
+        let mating_material = [
+            pieces::WPAWN,
+            pieces::BPAWN,
+            pieces::WROOK,
+            pieces::BROOK,
+            pieces::WQUEEN,
+            pieces::BQUEEN,
+        ]
+        .iter()
+        .fold(0, |board, piece| board | self.bitboards[*piece as usize]);
+        if mating_material != 0 {
+            return false;
+        }
+
+        let signature = self.material_signature();
+        if signature.minors(true) + signature.minors(false) <= 1 {
+            return true;
+        }
+        if signature.count(pieces::WKNIGHT) + signature.count(pieces::BKNIGHT) != 0 {
+            return false;
+        }
+        let bishops =
+            self.bitboards[pieces::WBISHOP as usize] | self.bitboards[pieces::BBISHOP as usize];
+        bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::pieces;
+    use crate::fen::{from_fen, startpos};
+
+    #[test]
+    fn counts_pieces() {
+        let signature = startpos().material_signature();
+        assert_eq!(signature.count(pieces::WPAWN), 8);
+        assert_eq!(signature.count(pieces::BKNIGHT), 2);
+        assert_eq!(signature.minors(false), 4);
+        assert_eq!(signature.majors(true), 3);
+        assert_eq!(signature.total(), 30);
+        assert_eq!(signature.to_string(), "KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP");
+
+        let endgame = from_fen("8/8/4k3/4r3/8/3RK3/4P3/8 w - - 0 1").unwrap();
+        assert_eq!(endgame.material_signature().to_string(), "KRPvKR");
+        assert_ne!(
+            endgame.material_signature().key(),
+            startpos().material_signature().key()
+        );
+    }
+
+    #[test]
+    fn dead_positions() {
+        let dead = [
+            "8/8/4k3/8/8/4K3/8/8 w - - 0 1",     // K vs K
+            "8/8/4k3/8/8/3NK3/8/8 w - - 0 1",    // K+N vs K
+            "8/8/4k3/8/8/3BK3/8/8 w - - 0 1",    // K+B vs K
+            "8/8/2b1k3/8/8/3BK3/8/8 w - - 0 1",  // bishops on light squares
+            "8/8/4k3/8/8/2B1K3/3B4/8 w - - 0 1", // both bishops on dark squares
+        ];
+        for fen in dead {
+            assert!(from_fen(fen).unwrap().is_insufficient_material(), "{}", fen);
+        }
+
+        let alive = [
+            "8/8/4k3/8/8/3PK3/8/8 w - - 0 1",   // a pawn can promote
+            "8/8/3bk3/8/8/3BK3/8/8 w - - 0 1",  // opposite coloured bishops
+            "8/8/4k3/8/8/2NNK3/8/8 w - - 0 1",  // K+N+N can be helpmated
+            "8/8/3nk3/8/8/3NK3/8/8 w - - 0 1",  // K+N vs K+N
+            "8/8/3nk3/8/8/3BK3/8/8 w - - 0 1",  // K+B vs K+N
+            "8/8/4k3/8/8/3BK3/3B4/8 w - - 0 1", // bishop pair
+        ];
+        for fen in alive {
+            assert!(
+                !from_fen(fen).unwrap().is_insufficient_material(),
+                "{}",
+                fen
+            );
+        }
+    }
+}
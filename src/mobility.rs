@@ -0,0 +1,127 @@
+use crate::bit_operations::shifts::{shift_ne, shift_nw, shift_se, shift_sw};
+use crate::bit_operations::{generate_from_index, pop_count, pop_ls1b};
+use crate::bitboard_gen::{
+    bishop_attacks, queen_attacks, rook_attacks, KING_TABLES, KNIGHT_TABLES,
+};
+use crate::board::{pieces, BoardData};
+use crate::eval::Score;
+
+/* ========================================
+*   Piece activity. Mobility counts the
+|   squares each knight, bishop, rook and
+*   queen attacks that are neither taken by
+|   its own pieces nor covered by an enemy
+*   pawn. The same attacks are checked
+|   against the squares around the enemy
+*   king, and the more pieces join in, the
+|   more each attacked square is worth.
+   ======================================   */
+
+// per safe square, by piece type: king, queen, bishop, knight, rook, pawn
+const MOBILITY: [Score; 6] = [
+    Score::new(0, 0),
+    Score::new(1, 2),
+    Score::new(5, 5),
+    Score::new(4, 4),
+    Score::new(2, 4),
+    Score::new(0, 0),
+];
+// the number of safe squares that scores zero, fewer is a penalty
+const MOBILITY_BASELINE: [i32; 6] = [0, 14, 7, 4, 7, 0];
+
+// how much one attacked square next to the enemy king is worth, by piece type
+const ATTACK_WEIGHTS: [i32; 6] = [0, 5, 2, 2, 3, 0];
+// percent of the attack that counts, by the number of pieces attacking.
+// a lone attacker is rarely dangerous
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+const KING_ATTACK: Score = Score::new(6, 1);
+
+impl BoardData {
+    // mobility and king attack terms, from white's point of view
+    pub fn activity_score(&self) -> Score {
+        self.side_activity(true) - self.side_activity(false)
+    }
+
+    fn side_activity(&self, white: bool) -> Score {
+        let (offset, own, enemy) = if white {
+            (0, pieces::ALLWHITE, pieces::ALLBLACK)
+        } else {
+            (6, pieces::ALLBLACK, pieces::ALLWHITE)
+        };
+        let own = self.bitboards[own as usize];
+        let occupancy = own | self.bitboards[enemy as usize];
+
+        let enemy_pawns = self.bitboards[(pieces::BPAWN - offset) as usize];
+        let enemy_pawn_attacks = if white {
+            shift_se(enemy_pawns) | shift_sw(enemy_pawns)
+        } else {
+            shift_ne(enemy_pawns) | shift_nw(enemy_pawns)
+        };
+        let safe = !own & !enemy_pawn_attacks;
+
+        let enemy_king = self.king_square(!white);
+        let king_zone = KING_TABLES[enemy_king as usize] | generate_from_index(enemy_king);
+
+        let mut score = Score::default();
+        let mut attackers = 0;
+        let mut attack_units = 0;
+        for piece_type in [
+            pieces::WQUEEN,
+            pieces::WBISHOP,
+            pieces::WKNIGHT,
+            pieces::WROOK,
+        ] {
+            let mut board = self.bitboards[(piece_type + offset) as usize];
+            while board != 0 {
+                let sqr = pop_ls1b(&mut board);
+                let attacks = match piece_type {
+                    pieces::WQUEEN => queen_attacks(occupancy, sqr),
+                    pieces::WBISHOP => bishop_attacks(occupancy, sqr),
+                    pieces::WKNIGHT => KNIGHT_TABLES[sqr as usize],
+                    _ => rook_attacks(occupancy, sqr),
+                };
+
+                let weight = MOBILITY[piece_type as usize];
+                let squares =
+                    pop_count(attacks & safe) as i32 - MOBILITY_BASELINE[piece_type as usize];
+                score += Score::new(weight.mg * squares, weight.eg * squares);
+
+                let zone_attacks = attacks & king_zone;
+                if zone_attacks != 0 {
+                    attackers += 1;
+                    attack_units +=
+                        ATTACK_WEIGHTS[piece_type as usize] * pop_count(zone_attacks) as i32;
+                }
+            }
+        }
+
+        let danger = attack_units * ATTACKER_SCALE[attackers.min(7)] / 100;
+        score + Score::new(KING_ATTACK.mg * danger, KING_ATTACK.eg * danger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::Score;
+    use crate::fen::{from_fen, startpos};
+
+    #[test]
+    fn startpos_is_level() {
+        assert_eq!(startpos().activity_score(), Score::default());
+    }
+
+    #[test]
+    fn central_knight_is_more_active() {
+        let center = from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let rim = from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert!(center.activity_score().mg > rim.activity_score().mg);
+    }
+
+    #[test]
+    fn attacking_the_king_scores() {
+        // knight and queen both hitting g7, against the same pieces far away
+        let attack = from_fen("6k1/5ppp/8/5NQ1/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        let distant = from_fen("6k1/5ppp/8/8/8/8/5PPP/NQ4K1 w - - 0 1").unwrap();
+        assert!(attack.activity_score().mg > distant.activity_score().mg + 20);
+    }
+}
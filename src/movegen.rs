@@ -1,16 +1,646 @@
-use crate::board::{BoardData, pieces};
+use crate::action::{actions, Action};
+use crate::bit_operations::{generate_from_index, ls1b, pop_count, pop_ls1b, shifts};
+use crate::bitboard_gen::{
+    bishop_attacks, queen_attacks, rook_attacks, BETWEEN_TABLES, KING_TABLES, KNIGHT_TABLES,
+    LINE_TABLES, PAWN_CAPTURE_TABLES, PAWN_PUSH_TABLES,
+};
+use crate::board::{pieces, BoardData, BK, BQ, WK, WQ};
+
+const RANK_2: u64 = 0xff00;
+const RANK_7: u64 = 0xff000000000000;
+
+// squares which must be empty for each castle
+const WK_EMPTY: u64 = 0x60; // f1, g1
+const WQ_EMPTY: u64 = 0xe; // b1, c1, d1
+const BK_EMPTY: u64 = 0x6000000000000000; // f8, g8
+const BQ_EMPTY: u64 = 0xe00000000000000; // b8, c8, d8
+
+// squares the king passes over for each castle, which may not be attacked
+const WK_SAFE: u64 = 0x60; // f1, g1
+const WQ_SAFE: u64 = 0xc; // c1, d1
+const BK_SAFE: u64 = 0x6000000000000000; // f8, g8
+const BQ_SAFE: u64 = 0xc00000000000000; // c8, d8
 
-impl BoardData {
 /* ========================================
-*   To optimize by not creating a new vector   
-|   and dumping all of the contents into a        
-*   "Main vector", each of the functions will
-|   take in a mutable reference to a vector 
-*   and add to it.              
+*   MoveMasks restricts the generated moves.
+|   check_mask is the set of squares a non-king
+*   piece may move to: everything if not in
+|   check, otherwise capturing the checker or
+*   blocking it. pinned pieces may only move
+|   along the line through them and the king.
+*   king_danger is every square attacked by the
+|   enemy, with our king removed from the board
+*   so it can't step backwards along a ray.
+|   targets limits where any piece may land,
+*   the enemy pieces when generating captures.
    ======================================   */
+struct MoveMasks {
+    legal: bool,
+    king_square: u8,
+    check_mask: u64,
+    pinned: u64,
+    king_danger: u64,
+    targets: u64,
+}
+
+impl MoveMasks {
+    // no restrictions, used for pseudo-legal generation
+    fn pseudolegal() -> MoveMasks {
+        MoveMasks {
+            legal: false,
+            king_square: 0,
+            check_mask: !0,
+            pinned: 0,
+            king_danger: 0,
+            targets: !0,
+        }
+    }
+
+    // restricts a piece's targets to its pin line if it is pinned
+    #[inline]
+    fn pin_mask(&self, from: u8) -> u64 {
+        if self.pinned & generate_from_index(from) != 0 {
+            LINE_TABLES[self.king_square as usize][from as usize]
+        } else {
+            !0
+        }
+    }
+}
+
+impl BoardData {
+    /* ========================================
+    *   To optimize by not creating a new vector
+    |   and dumping all of the contents into a
+    *   "Main vector", each of the functions will
+    |   take in a mutable reference to a vector
+    *   and add to it.
+       ======================================   */
+
+    // generates every pseudo-legal move for the side to move.
+    // these moves may leave the king in check.
+    pub fn generate_pseudolegal(&self, add_to: &mut Vec<Action>) {
+        let masks = MoveMasks::pseudolegal();
+        self.generate_all_pawnmoves(add_to, &masks);
+        self.generate_all_knightmoves(add_to, &masks);
+        self.generate_all_bishopmoves(add_to, &masks);
+        self.generate_all_rookmoves(add_to, &masks);
+        self.generate_all_queenmoves(add_to, &masks);
+        self.generate_all_kingmoves(add_to, &masks);
+        self.generate_all_castles(add_to, &masks);
+    }
+
+    // generates only moves which do not leave the king in check.
+    // checkers and pins are calculated up front, so no move needs to be made and tested.
+    pub fn generate_legal_moves(&self, add_to: &mut Vec<Action>) {
+        let masks = self.legal_masks();
+        self.generate_all_kingmoves(add_to, &masks);
+
+        // in double check, only the king can move
+        if masks.check_mask == 0 {
+            return;
+        }
+        self.generate_all_pawnmoves(add_to, &masks);
+        self.generate_all_knightmoves(add_to, &masks);
+        self.generate_all_bishopmoves(add_to, &masks);
+        self.generate_all_rookmoves(add_to, &masks);
+        self.generate_all_queenmoves(add_to, &masks);
+        self.generate_all_castles(add_to, &masks);
+    }
+
+    // legal captures, en passant and promotions (including quiet ones), for quiescence search
+    pub fn generate_legal_captures(&self, add_to: &mut Vec<Action>) {
+        let mut masks = self.legal_masks();
+        masks.targets = self.enemy_occupancy();
+        self.generate_all_kingmoves(add_to, &masks);
+
+        if masks.check_mask == 0 {
+            return;
+        }
+        self.generate_all_pawncaptures(add_to, &masks);
+        self.generate_all_knightmoves(add_to, &masks);
+        self.generate_all_bishopmoves(add_to, &masks);
+        self.generate_all_rookmoves(add_to, &masks);
+        self.generate_all_queenmoves(add_to, &masks);
+    }
+
+    fn legal_masks(&self) -> MoveMasks {
+        let offset = self.piece_offset();
+        let enemy_offset = 6 - offset;
+        let friendly = self.friendly_occupancy();
+        let enemy = self.enemy_occupancy();
+        let occupancy = friendly | enemy;
+        let king_square = self.king_square(self.to_move);
+
+        let checkers = self.checkers();
+        let check_mask = match pop_count(checkers) {
+            0 => !0,
+            1 => checkers | BETWEEN_TABLES[king_square as usize][ls1b(checkers) as usize],
+            _ => 0,
+        };
+
+        // look through our own pieces from the king to find sliders that would give check
+        let enemy_queens = self.bitboards[(pieces::WQUEEN + enemy_offset) as usize];
+        let enemy_diagonals =
+            self.bitboards[(pieces::WBISHOP + enemy_offset) as usize] | enemy_queens;
+        let enemy_orthogonals =
+            self.bitboards[(pieces::WROOK + enemy_offset) as usize] | enemy_queens;
+        let mut snipers = (bishop_attacks(enemy, king_square) & enemy_diagonals)
+            | (rook_attacks(enemy, king_square) & enemy_orthogonals);
+        let mut pinned = 0;
+        while snipers != 0 {
+            let sniper = pop_ls1b(&mut snipers);
+            let blockers = BETWEEN_TABLES[king_square as usize][sniper as usize] & occupancy;
+            if pop_count(blockers) == 1 {
+                pinned |= blockers & friendly;
+            }
+        }
+
+        // squares attacked by the enemy, ignoring our king as a blocker
+        let without_king = occupancy & !generate_from_index(king_square);
+        let king_danger = self.attacked_squares(!self.to_move, without_king);
+
+        MoveMasks {
+            legal: true,
+            king_square,
+            check_mask,
+            pinned,
+            king_danger,
+            targets: !0,
+        }
+    }
+
+    // en passant can remove two pieces from the same rank at once, which
+    // the pin mask can't see, so it is tested by removing both pawns
+    fn passant_is_legal(&self, from: u8, passant_square: u8, masks: &MoveMasks) -> bool {
+        let captured = if self.to_move {
+            passant_square - 8
+        } else {
+            passant_square + 8
+        };
+        if masks.check_mask & (generate_from_index(passant_square) | generate_from_index(captured))
+            == 0
+        {
+            return false;
+        }
+        !self.passant_exposes_king(from, passant_square, masks.king_square)
+    }
+
+    fn passant_exposes_king(&self, from: u8, passant_square: u8, king_square: u8) -> bool {
+        let captured = if self.to_move {
+            passant_square - 8
+        } else {
+            passant_square + 8
+        };
+        let occupancy =
+            self.occupancy() ^ generate_from_index(from) ^ generate_from_index(captured)
+                | generate_from_index(passant_square);
+
+        self.attackers_to(king_square, occupancy) & self.enemy_occupancy() & occupancy != 0
+    }
+
+    // whether the side to move has a legal en passant capture. the passant square
+    // is only kept and hashed when it does, so a double push that can't be taken
+    // doesn't stop the position from repeating
+    pub fn can_capture_passant(&self) -> bool {
+        let passant_square = match self.passant_square {
+            Some(sqr) => sqr,
+            None => return false,
+        };
+        // our pawns are where an enemy pawn on the passant square would attack
+        let color = if self.to_move { 1 } else { 0 };
+        let pawns = self.bitboards[(pieces::WPAWN + self.piece_offset()) as usize];
+        let mut capturers = PAWN_CAPTURE_TABLES[color][passant_square as usize] & pawns;
+        let king_square = self.king_square(self.to_move);
+        while capturers != 0 {
+            let from = pop_ls1b(&mut capturers);
+            if !self.passant_exposes_king(from, passant_square, king_square) {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[inline]
+    fn friendly_occupancy(&self) -> u64 {
+        if self.to_move {
+            self.bitboards[pieces::ALLWHITE as usize]
+        } else {
+            self.bitboards[pieces::ALLBLACK as usize]
+        }
+    }
+
+    #[inline]
+    fn enemy_occupancy(&self) -> u64 {
+        if self.to_move {
+            self.bitboards[pieces::ALLBLACK as usize]
+        } else {
+            self.bitboards[pieces::ALLWHITE as usize]
+        }
+    }
+
+    // offset to get from a white piece index to the side to move's piece index
+    #[inline]
+    fn piece_offset(&self) -> u8 {
+        if self.to_move {
+            0
+        } else {
+            6
+        }
+    }
+
+    fn generate_all_pawnmoves(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let pawns = self.bitboards[(pieces::WPAWN + self.piece_offset()) as usize];
+        let empty = !(self.friendly_occupancy() | self.enemy_occupancy());
+        let enemy = self.enemy_occupancy();
+        let color = if self.to_move { 0 } else { 1 };
+        let (start_rank, promote_rank) = if self.to_move {
+            (RANK_2, RANK_7)
+        } else {
+            (RANK_7, RANK_2)
+        };
+
+        // the push and capture tables do not contain promotions, so promoting pawns
+        // are generated separately
+        let mut normal_pawns = pawns & !promote_rank;
+        while normal_pawns != 0 {
+            let from = pop_ls1b(&mut normal_pawns);
+            let allowed = masks.check_mask & masks.pin_mask(from);
+            let single_push = PAWN_PUSH_TABLES[color][from as usize] & empty;
+            push_normal_moves(from, single_push & allowed, add_to);
+
+            if single_push != 0 && generate_from_index(from) & start_rank != 0 {
+                let double_push =
+                    PAWN_PUSH_TABLES[color][single_push.trailing_zeros() as usize] & empty;
+                push_normal_moves(from, double_push & allowed, add_to);
+            }
+
+            let captures = PAWN_CAPTURE_TABLES[color][from as usize];
+            push_normal_moves(from, captures & enemy & allowed, add_to);
+
+            if let Some(sqr) = self.passant_square {
+                if captures & generate_from_index(sqr) != 0
+                    && (!masks.legal || self.passant_is_legal(from, sqr, masks))
+                {
+                    add_to.push(actions::new(from, sqr, actions::PASSANT, 0));
+                }
+            }
+        }
+
+        self.generate_all_promotions(pawns & promote_rank, add_to, masks);
+    }
+
+    // like generate_all_pawnmoves, without the pushes that don't promote
+    fn generate_all_pawncaptures(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let pawns = self.bitboards[(pieces::WPAWN + self.piece_offset()) as usize];
+        let enemy = self.enemy_occupancy();
+        let color = if self.to_move { 0 } else { 1 };
+        let promote_rank = if self.to_move { RANK_7 } else { RANK_2 };
+
+        let mut normal_pawns = pawns & !promote_rank;
+        while normal_pawns != 0 {
+            let from = pop_ls1b(&mut normal_pawns);
+            let captures = PAWN_CAPTURE_TABLES[color][from as usize];
+            let allowed = masks.check_mask & masks.pin_mask(from);
+            push_normal_moves(from, captures & enemy & allowed, add_to);
+
+            if let Some(sqr) = self.passant_square {
+                if captures & generate_from_index(sqr) != 0
+                    && self.passant_is_legal(from, sqr, masks)
+                {
+                    add_to.push(actions::new(from, sqr, actions::PASSANT, 0));
+                }
+            }
+        }
+
+        self.generate_all_promotions(pawns & promote_rank, add_to, masks);
+    }
+
+    fn generate_all_promotions(
+        &self,
+        mut promoting_pawns: u64,
+        add_to: &mut Vec<Action>,
+        masks: &MoveMasks,
+    ) {
+        let empty = !(self.friendly_occupancy() | self.enemy_occupancy());
+        let enemy = self.enemy_occupancy();
+        while promoting_pawns != 0 {
+            let from = pop_ls1b(&mut promoting_pawns);
+            let pawn_board = generate_from_index(from);
+            let targets = if self.to_move {
+                (shifts::shift_n(pawn_board) & empty)
+                    | ((shifts::shift_nw(pawn_board) | shifts::shift_ne(pawn_board)) & enemy)
+            } else {
+                (shifts::shift_s(pawn_board) & empty)
+                    | ((shifts::shift_sw(pawn_board) | shifts::shift_se(pawn_board)) & enemy)
+            };
+            push_promotions(
+                from,
+                targets & masks.check_mask & masks.pin_mask(from),
+                add_to,
+            );
+        }
+    }
+
+    fn generate_all_knightmoves(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let idx = pieces::WKNIGHT + self.piece_offset();
+        // not our own pieces, answering any check, and only captures when masks.targets says so
+        let allowed = !self.friendly_occupancy() & masks.check_mask & masks.targets;
+        let mut knights = self.bitboards[idx as usize];
+        while knights != 0 {
+            let from = pop_ls1b(&mut knights);
+            let targets = KNIGHT_TABLES[from as usize] & allowed;
+            push_normal_moves(from, targets & masks.pin_mask(from), add_to);
+        }
+    }
+
+    fn generate_all_bishopmoves(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let idx = pieces::WBISHOP + self.piece_offset();
+        let allowed = !self.friendly_occupancy() & masks.check_mask & masks.targets;
+        let occupancy = self.friendly_occupancy() | self.enemy_occupancy();
+        let mut bishops = self.bitboards[idx as usize];
+        while bishops != 0 {
+            let from = pop_ls1b(&mut bishops);
+            let targets = bishop_attacks(occupancy, from) & allowed;
+            push_normal_moves(from, targets & masks.pin_mask(from), add_to);
+        }
+    }
+
+    fn generate_all_rookmoves(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let idx = pieces::WROOK + self.piece_offset();
+        let allowed = !self.friendly_occupancy() & masks.check_mask & masks.targets;
+        let occupancy = self.friendly_occupancy() | self.enemy_occupancy();
+        let mut rooks = self.bitboards[idx as usize];
+        while rooks != 0 {
+            let from = pop_ls1b(&mut rooks);
+            let targets = rook_attacks(occupancy, from) & allowed;
+            push_normal_moves(from, targets & masks.pin_mask(from), add_to);
+        }
+    }
+
+    fn generate_all_queenmoves(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let idx = pieces::WQUEEN + self.piece_offset();
+        let allowed = !self.friendly_occupancy() & masks.check_mask & masks.targets;
+        let occupancy = self.friendly_occupancy() | self.enemy_occupancy();
+        let mut queens = self.bitboards[idx as usize];
+        while queens != 0 {
+            let from = pop_ls1b(&mut queens);
+            let targets = queen_attacks(occupancy, from) & allowed;
+            push_normal_moves(from, targets & masks.pin_mask(from), add_to);
+        }
+    }
+
+    fn generate_all_kingmoves(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        let idx = pieces::WKING + self.piece_offset();
+        let not_friendly = !self.friendly_occupancy() & !masks.king_danger & masks.targets;
+        let mut kings = self.bitboards[idx as usize];
+        while kings != 0 {
+            let from = pop_ls1b(&mut kings);
+            push_normal_moves(from, KING_TABLES[from as usize] & not_friendly, add_to);
+        }
+    }
+
+    // pseudo-legal castles only check castle rights and that the squares between king and
+    // rook are empty. Legal castles also can't start in check or pass through an attacked square.
+    fn generate_all_castles(&self, add_to: &mut Vec<Action>, masks: &MoveMasks) {
+        if masks.legal && masks.check_mask != !0 {
+            return;
+        }
+        let occupancy = self.friendly_occupancy() | self.enemy_occupancy();
+        let danger = masks.king_danger;
+        if self.to_move {
+            if self.castle_rights_mask & WK != 0
+                && occupancy & WK_EMPTY == 0
+                && danger & WK_SAFE == 0
+            {
+                add_to.push(actions::new(4, 6, actions::CASTLE, 0));
+            }
+            if self.castle_rights_mask & WQ != 0
+                && occupancy & WQ_EMPTY == 0
+                && danger & WQ_SAFE == 0
+            {
+                add_to.push(actions::new(4, 2, actions::CASTLE, 0));
+            }
+        } else {
+            if self.castle_rights_mask & BK != 0
+                && occupancy & BK_EMPTY == 0
+                && danger & BK_SAFE == 0
+            {
+                add_to.push(actions::new(60, 62, actions::CASTLE, 0));
+            }
+            if self.castle_rights_mask & BQ != 0
+                && occupancy & BQ_EMPTY == 0
+                && danger & BQ_SAFE == 0
+            {
+                add_to.push(actions::new(60, 58, actions::CASTLE, 0));
+            }
+        }
+    }
+}
+
+#[inline]
+fn push_normal_moves(from: u8, mut targets: u64, add_to: &mut Vec<Action>) {
+    while targets != 0 {
+        let to = pop_ls1b(&mut targets);
+        add_to.push(actions::new(from, to, actions::NORMAL, 0));
+    }
+}
+
+#[inline]
+fn push_promotions(from: u8, mut targets: u64, add_to: &mut Vec<Action>) {
+    while targets != 0 {
+        let to = pop_ls1b(&mut targets);
+        for promote_to in [
+            actions::PR_QUEEN,
+            actions::PR_KNIGHT,
+            actions::PR_BISHOP,
+            actions::PR_ROOK,
+        ] {
+            add_to.push(actions::new(from, to, actions::PROMOTION, promote_to));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action::{actions, Action, Move};
+    use crate::board::{pieces, BoardData};
+    use crate::fen::from_fen;
+    use crate::testing::PERFT_POSITIONS;
+
+    fn legal_uci(fen: &str) -> Vec<String> {
+        let mut moves = Vec::new();
+        from_fen(fen).unwrap().generate_legal_moves(&mut moves);
+        let mut uci: Vec<String> = moves.iter().map(|action| action.to_uci()).collect();
+        uci.sort();
+        uci
+    }
+
+    fn moves_from(fen: &str, square: &str) -> Vec<String> {
+        legal_uci(fen)
+            .into_iter()
+            .filter(|uci| uci.starts_with(square))
+            .collect()
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        assert_eq!(
+            moves_from("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1", "e2"),
+            ["e2e3", "e2e4", "e2e5", "e2e6", "e2e7"]
+        );
+        assert_eq!(
+            moves_from("4k3/8/8/b7/8/2B5/8/4K3 w - - 0 1", "c3"),
+            ["c3a5", "c3b4", "c3d2"]
+        );
+        // a pinned knight can never stay on the line
+        assert!(moves_from("4k3/8/8/b7/8/8/3N4/4K3 w - - 0 1", "d2").is_empty());
+        // a pawn pinned along a file may push but not capture
+        assert_eq!(
+            moves_from("4r1k1/8/8/8/3p4/4P3/8/4K3 w - - 0 1", "e3"),
+            ["e3e4"]
+        );
+    }
+
+    #[test]
+    fn passant_may_not_uncover_the_king() {
+        // taking on d6 removes both pawns from the fifth rank and exposes the king to the rook
+        assert!(!legal_uci("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1").contains(&"e5d6".to_string()));
+        assert!(legal_uci("8/8/8/K2pP3/8/8/8/7k w - d6 0 1").contains(&"e5d6".to_string()));
+        assert!(!legal_uci("7K/8/8/8/1R2Pp1k/8/8/8 b - e3 0 1").contains(&"f4e3".to_string()));
+    }
+
+    #[test]
+    fn castling_avoids_attacked_squares() {
+        let castles = |fen: &str| -> Vec<String> {
+            let mut moves = Vec::new();
+            let board = from_fen(fen).unwrap();
+            board.generate_legal_moves(&mut moves);
+            let mut uci: Vec<String> = moves
+                .iter()
+                .filter(|action| action.move_type() == actions::CASTLE)
+                .map(|action| action.to_uci())
+                .collect();
+            uci.sort();
+            uci
+        };
+        assert_eq!(
+            castles("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"),
+            ["e1c1", "e1g1"]
+        );
+        // out of check
+        assert!(castles("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1").is_empty());
+        // through f1
+        assert_eq!(castles("r3k2r/8/b7/8/8/8/8/R3K2R w KQkq - 0 1"), ["e1c1"]);
+        // into g1
+        assert_eq!(castles("r3k2r/8/8/8/8/7n/8/R3K2R w KQkq - 0 1"), ["e1c1"]);
+        // the rook may pass over an attacked b1
+        assert_eq!(
+            castles("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1"),
+            ["e1c1", "e1g1"]
+        );
+        // through f8 for black
+        assert_eq!(castles("r3k2r/8/8/8/8/B7/8/R3K2R b KQkq - 0 1"), ["e8c8"]);
+    }
+
+    #[test]
+    fn only_the_king_moves_in_double_check() {
+        // the bishop could take the knight, but the rook would still give check
+        assert_eq!(
+            legal_uci("4r2k/8/8/8/8/3n4/8/1B2K3 w - - 0 1"),
+            ["e1d1", "e1d2", "e1f1"]
+        );
+    }
+
+    // a pseudo-legal move is legal if the king isn't left in check, and a castle
+    // also may not start in check or cross an attacked square
+    fn assert_pseudolegal_filters_to_legal(board: &mut BoardData) {
+        let mut pseudolegal = Vec::new();
+        board.generate_pseudolegal(&mut pseudolegal);
+        let side = board.to_move;
+        let mut filtered: Vec<Action> = pseudolegal
+            .into_iter()
+            .filter(|&action| {
+                if action.move_type() == actions::CASTLE {
+                    let crossed = (action.move_from() + action.move_to()) / 2;
+                    if board.in_check() || board.is_square_attacked(crossed, !side) {
+                        return false;
+                    }
+                }
+                board.make_move(action);
+                let safe = !board.is_square_attacked(board.king_square(side), !side);
+                board.undo_move(action);
+                safe
+            })
+            .collect();
+        let mut legal = Vec::new();
+        board.generate_legal_moves(&mut legal);
+
+        filtered.sort_unstable();
+        legal.sort_unstable();
+        assert_eq!(filtered, legal, "{}", board.to_fen());
+    }
+
+    #[test]
+    fn pseudolegal_moves_filtered_by_king_safety_are_legal() {
+        for fen in PERFT_POSITIONS {
+            let mut board = from_fen(fen).unwrap();
+            assert_pseudolegal_filters_to_legal(&mut board);
+            let mut moves = Vec::new();
+            board.generate_legal_moves(&mut moves);
+            for action in moves {
+                board.make_move(action);
+                assert_pseudolegal_filters_to_legal(&mut board);
+                board.undo_move(action);
+            }
+        }
+    }
+
+    fn assert_captures_match(board: &BoardData) {
+        let mut moves = Vec::new();
+        board.generate_legal_moves(&mut moves);
+        let mut expected: Vec<Action> = moves
+            .into_iter()
+            .filter(|action| {
+                action.move_type() == actions::PROMOTION
+                    || action.move_type() == actions::PASSANT
+                    || board.mailbox[action.move_to() as usize] != pieces::NOPIECE
+            })
+            .collect();
+        let mut captures = Vec::new();
+        board.generate_legal_captures(&mut captures);
+
+        expected.sort_unstable();
+        captures.sort_unstable();
+        assert_eq!(captures, expected, "{}", board.to_fen());
+    }
+
+    // every capture, en passant and promotion of generate_legal_moves, and nothing else,
+    // two plies deep so that positions in check are covered too
+    #[test]
+    fn captures_are_the_capturing_legal_moves() {
+        for fen in PERFT_POSITIONS {
+            let mut board = from_fen(fen).unwrap();
+            assert_captures_match(&board);
+            let mut moves = Vec::new();
+            board.generate_legal_moves(&mut moves);
+            for action in moves {
+                board.make_move(action);
+                assert_captures_match(&board);
+                board.undo_move(action);
+            }
+        }
+    }
 
-   fn generate_all_knightmoves(&self, add_to: &mut [u16]){
-      let idx = if self.to_move {pieces::WKNIGHT} else {pieces::BKNIGHT};
-      
-   } 
-}
\ No newline at end of file
+    #[test]
+    fn captures_include_passant_and_quiet_promotions() {
+        let board = from_fen("1n2k3/P7/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let mut captures = Vec::new();
+        board.generate_legal_captures(&mut captures);
+        let mut uci: Vec<String> = captures.iter().map(|action| action.to_uci()).collect();
+        uci.sort();
+        assert_eq!(
+            uci,
+            ["a7a8b", "a7a8n", "a7a8q", "a7a8r", "a7b8b", "a7b8n", "a7b8q", "a7b8r", "e5d6"]
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use std::fmt;
+
+use crate::action::{actions, Action, Move};
+use crate::board::{pieces, BoardData};
+use crate::fen::idx_to_coordsquare;
+
+/* ========================================
+*   Converting moves to and from text.
+|   Moves coming from a gui or a log file
+*   only name the squares, so the move type
+|   (castle, en passant, promotion) is
+*   worked out from the position. Action
+|   to UCI text is Move::to_uci.
+   ======================================   */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveParseError {
+    InvalidFormat(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveParseError::InvalidFormat(text) => write!(f, "invalid move format: {}", text),
+            MoveParseError::IllegalMove(text) => write!(f, "illegal move: {}", text),
+            MoveParseError::AmbiguousMove(text) => write!(f, "ambiguous move: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+// "e4" -> 28
+pub fn square_from_str(square: &str) -> Option<u8> {
+    let bytes = square.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let file = bytes[0].wrapping_sub(b'a');
+    let rank = bytes[1].wrapping_sub(b'1');
+    if file < 8 && rank < 8 {
+        Some(rank * 8 + file)
+    } else {
+        None
+    }
+}
+
+impl BoardData {
+    // parses long algebraic notation such as e2e4, e7e8q or e1g1.
+    // the move must be legal in this position.
+    pub fn parse_uci_move(&self, move_str: &str) -> Result<Action, MoveParseError> {
+        let invalid = || MoveParseError::InvalidFormat(move_str.to_string());
+        if !move_str.is_ascii() || !(4..=5).contains(&move_str.len()) {
+            return Err(invalid());
+        }
+        let from = square_from_str(&move_str[0..2]).ok_or_else(invalid)?;
+        let to = square_from_str(&move_str[2..4]).ok_or_else(invalid)?;
+        let promotion = match move_str[4..].chars().next() {
+            None => None,
+            Some('q') | Some('Q') => Some(actions::PR_QUEEN),
+            Some('n') | Some('N') => Some(actions::PR_KNIGHT),
+            Some('b') | Some('B') => Some(actions::PR_BISHOP),
+            Some('r') | Some('R') => Some(actions::PR_ROOK),
+            Some(_) => return Err(invalid()),
+        };
+
+        let moving_piece = self.mailbox[from as usize];
+        let action = if let Some(promote_to) = promotion {
+            actions::new(from, to, actions::PROMOTION, promote_to)
+        } else if (moving_piece == pieces::WKING || moving_piece == pieces::BKING)
+            && from.abs_diff(to) == 2
+        {
+            actions::new(from, to, actions::CASTLE, 0)
+        } else if (moving_piece == pieces::WPAWN || moving_piece == pieces::BPAWN)
+            && self.passant_square == Some(to)
+            && (from & 7) != (to & 7)
+        {
+            actions::new(from, to, actions::PASSANT, 0)
+        } else {
+            actions::new(from, to, actions::NORMAL, 0)
+        };
+
+        let mut moves = Vec::new();
+        self.generate_legal_moves(&mut moves);
+        if moves.contains(&action) {
+            Ok(action)
+        } else {
+            Err(MoveParseError::IllegalMove(move_str.to_string()))
+        }
+    }
+}
+
+// piece type regardless of color: king, queen, bishop, knight, rook, pawn
+#[inline]
+fn piece_type(piece: u8) -> u8 {
+    piece % 6
+}
+
+fn piece_letter(piece_type: u8) -> char {
+    match piece_type {
+        pieces::WKING => 'K',
+        pieces::WQUEEN => 'Q',
+        pieces::WBISHOP => 'B',
+        pieces::WKNIGHT => 'N',
+        pieces::WROOK => 'R',
+        _ => 'P',
+    }
+}
+
+fn piece_type_from_letter(letter: char) -> Option<u8> {
+    match letter {
+        'K' => Some(pieces::WKING),
+        'Q' => Some(pieces::WQUEEN),
+        'B' => Some(pieces::WBISHOP),
+        'N' => Some(pieces::WKNIGHT),
+        'R' => Some(pieces::WROOK),
+        // never written for pawn moves, but some older files do
+        'P' => Some(pieces::WPAWN),
+        _ => None,
+    }
+}
+
+fn promotion_from_letter(letter: char) -> Option<u16> {
+    match letter.to_ascii_uppercase() {
+        'Q' => Some(actions::PR_QUEEN),
+        'N' => Some(actions::PR_KNIGHT),
+        'B' => Some(actions::PR_BISHOP),
+        'R' => Some(actions::PR_ROOK),
+        _ => None,
+    }
+}
+
+fn promotion_letter(promote_to: u16) -> char {
+    match promote_to {
+        actions::PR_QUEEN => 'Q',
+        actions::PR_KNIGHT => 'N',
+        actions::PR_BISHOP => 'B',
+        _ => 'R',
+    }
+}
+
+/* ========================================
+*   Standard Algebraic Notation. Output
+|   follows the PGN standard: Nbd7, exd6,
+*   O-O-O, e8=Q+, Qxf7#. Input is lenient
+|   and also takes 0-0, a missing x, e8Q,
+*   a P before pawn moves, annotations
+|   like !? and "e.p.". Check and mate
+*   marks are dropped unchecked, so a
+|   wrong "+" is not an error.
+   ======================================   */
+
+impl BoardData {
+    // the move must be legal in this position
+    pub fn move_to_san(&mut self, action: Action) -> String {
+        let mut moves = Vec::new();
+        self.generate_legal_moves(&mut moves);
+
+        let from = action.move_from();
+        let to = action.move_to();
+        let moving_type = piece_type(self.mailbox[from as usize]);
+        let mut san = String::new();
+
+        if action.move_type() == actions::CASTLE {
+            san += if to & 7 == 6 { "O-O" } else { "O-O-O" };
+        } else {
+            let is_capture = self.mailbox[to as usize] != pieces::NOPIECE
+                || action.move_type() == actions::PASSANT;
+            let from_square = idx_to_coordsquare(from);
+
+            if moving_type == pieces::WPAWN {
+                if is_capture {
+                    san.push_str(&from_square[0..1]);
+                }
+            } else {
+                san.push(piece_letter(moving_type));
+
+                // other pieces of the same type that can reach the same square
+                let others: Vec<u8> = moves
+                    .iter()
+                    .filter(|other| {
+                        other.move_to() == to
+                            && other.move_from() != from
+                            && piece_type(self.mailbox[other.move_from() as usize]) == moving_type
+                    })
+                    .map(|other| other.move_from())
+                    .collect();
+                if !others.is_empty() {
+                    if others.iter().all(|sqr| sqr & 7 != from & 7) {
+                        san.push_str(&from_square[0..1]);
+                    } else if others.iter().all(|sqr| sqr >> 3 != from >> 3) {
+                        san.push_str(&from_square[1..2]);
+                    } else {
+                        san.push_str(&from_square);
+                    }
+                }
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&idx_to_coordsquare(to));
+            if action.move_type() == actions::PROMOTION {
+                san.push('=');
+                san.push(promotion_letter(action.promote_to()));
+            }
+        }
+
+        self.make_move(action);
+        if self.in_check() {
+            let mut replies = Vec::new();
+            self.generate_legal_moves(&mut replies);
+            san.push(if replies.is_empty() { '#' } else { '+' });
+        }
+        self.undo_move(action);
+        san
+    }
+
+    // SAN with en passant captures marked, e.g. "exd6 e.p.". PGN export should use move_to_san
+    pub fn move_to_san_with_passant_marker(&mut self, action: Action) -> String {
+        let san = self.move_to_san(action);
+        if action.move_type() == actions::PASSANT {
+            san + " e.p."
+        } else {
+            san
+        }
+    }
+
+    pub fn parse_san_move(&self, san: &str) -> Result<Action, MoveParseError> {
+        let invalid = || MoveParseError::InvalidFormat(san.to_string());
+
+        let mut text = san.trim();
+        if let Some(stripped) = text.strip_suffix("e.p.") {
+            text = stripped.trim_end();
+        }
+        let text = text.trim_end_matches(['+', '#', '!', '?']);
+
+        let mut moves = Vec::new();
+        self.generate_legal_moves(&mut moves);
+
+        // castling
+        let castle_to = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_to {
+            return moves
+                .into_iter()
+                .find(|action| {
+                    action.move_type() == actions::CASTLE && action.move_to() & 7 == file
+                })
+                .ok_or_else(|| MoveParseError::IllegalMove(san.to_string()));
+        }
+
+        // captures and promotion markers carry no information we need
+        let mut chars: Vec<char> = text
+            .chars()
+            .filter(|c| *c != 'x' && *c != '=' && *c != ':')
+            .collect();
+
+        let moving_type = match chars.first().copied().and_then(piece_type_from_letter) {
+            Some(piece) => {
+                chars.remove(0);
+                piece
+            }
+            None => pieces::WPAWN,
+        };
+
+        let mut promotion = None;
+        if moving_type == pieces::WPAWN && chars.len() > 2 {
+            if let Some(promote_to) = chars.last().copied().and_then(promotion_from_letter) {
+                // "b" alone at the end could be a file, but a square must come before it
+                if chars[chars.len() - 2].is_ascii_digit() {
+                    promotion = Some(promote_to);
+                    chars.pop();
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let dest: String = chars[chars.len() - 2..].iter().collect();
+        let to = square_from_str(&dest).ok_or_else(invalid)?;
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(*c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(*c as u8 - b'1'),
+                _ => return Err(invalid()),
+            }
+        }
+
+        let candidates: Vec<Action> = moves
+            .into_iter()
+            .filter(|action| {
+                let from = action.move_from();
+                action.move_to() == to
+                    && action.move_type() != actions::CASTLE
+                    && piece_type(self.mailbox[from as usize]) == moving_type
+                    && from_file.is_none_or(|file| from & 7 == file)
+                    && from_rank.is_none_or(|rank| from >> 3 == rank)
+                    && match promotion {
+                        Some(promote_to) => {
+                            action.move_type() == actions::PROMOTION
+                                && action.promote_to() == promote_to
+                        }
+                        None => action.move_type() != actions::PROMOTION,
+                    }
+            })
+            .collect();
+
+        match candidates.len() {
+            0 => Err(MoveParseError::IllegalMove(san.to_string())),
+            1 => Ok(candidates[0]),
+            _ => Err(MoveParseError::AmbiguousMove(san.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen;
+    use crate::testing::PERFT_POSITIONS;
+
+    fn san(fen: &str, uci: &str) -> String {
+        let mut board = from_fen(fen).unwrap();
+        let action = board.parse_uci_move(uci).unwrap();
+        board.move_to_san(action)
+    }
+
+    fn parse_san(fen: &str, san: &str) -> Result<String, MoveParseError> {
+        let board = from_fen(fen).unwrap();
+        board.parse_san_move(san).map(|action| action.to_uci())
+    }
+
+    #[test]
+    fn uci_move_types() {
+        let castling = from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(
+            castling.parse_uci_move("e1g1").unwrap().move_type(),
+            actions::CASTLE
+        );
+        assert_eq!(
+            castling.parse_uci_move("e1c1").unwrap().move_type(),
+            actions::CASTLE
+        );
+        assert_eq!(
+            castling.parse_uci_move("e1f1").unwrap().move_type(),
+            actions::NORMAL
+        );
+
+        let passant = from_fen("4k3/8/8/4Pp2/8/8/8/4K3 w - f6 0 1").unwrap();
+        assert_eq!(
+            passant.parse_uci_move("e5f6").unwrap().move_type(),
+            actions::PASSANT
+        );
+        assert_eq!(
+            passant.parse_uci_move("e5e6").unwrap().move_type(),
+            actions::NORMAL
+        );
+
+        let promotion = from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        for (text, promote_to) in [
+            ("b7b8q", actions::PR_QUEEN),
+            ("b7b8N", actions::PR_KNIGHT),
+            ("b7b8b", actions::PR_BISHOP),
+            ("b7b8r", actions::PR_ROOK),
+        ] {
+            let action = promotion.parse_uci_move(text).unwrap();
+            assert_eq!(action.move_type(), actions::PROMOTION);
+            assert_eq!(action.promote_to(), promote_to);
+        }
+        // a pawn reaching the last rank must say what it becomes
+        assert_eq!(
+            promotion.parse_uci_move("b7b8"),
+            Err(MoveParseError::IllegalMove(String::from("b7b8")))
+        );
+    }
+
+    #[test]
+    fn uci_move_errors() {
+        let board = crate::fen::startpos();
+        assert_eq!(
+            board.parse_uci_move("e2e5"),
+            Err(MoveParseError::IllegalMove(String::from("e2e5")))
+        );
+        assert_eq!(
+            board.parse_uci_move("e2e4q"),
+            Err(MoveParseError::IllegalMove(String::from("e2e4q")))
+        );
+        for text in ["", "e2", "e9e4", "i2e4", "e7e8k", "e2e4qq", "e2-e4"] {
+            assert_eq!(
+                board.parse_uci_move(text),
+                Err(MoveParseError::InvalidFormat(text.to_string())),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn disambiguation() {
+        // two rooks on the a file
+        let rooks = "4k3/8/8/8/8/R7/8/R3K3 w - - 0 1";
+        assert_eq!(san(rooks, "a1a2"), "R1a2");
+        assert_eq!(san(rooks, "a3a2"), "R3a2");
+        assert_eq!(parse_san(rooks, "R1a2").unwrap(), "a1a2");
+        assert_eq!(parse_san(rooks, "R3a2").unwrap(), "a3a2");
+        assert_eq!(
+            parse_san(rooks, "Ra2"),
+            Err(MoveParseError::AmbiguousMove(String::from("Ra2")))
+        );
+
+        // two knights on the first rank
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san(knights, "b1d2"), "Nbd2");
+        assert_eq!(san(knights, "f1d2"), "Nfd2");
+        assert_eq!(parse_san(knights, "Nfd2").unwrap(), "f1d2");
+
+        // a queen sharing a file with one and a rank with another needs both
+        let queens = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san(queens, "a1b2"), "Qa1b2");
+        assert_eq!(parse_san(queens, "Qa1b2").unwrap(), "a1b2");
+        assert_eq!(san(queens, "a3a2"), "Q3a2");
+    }
+
+    #[test]
+    fn special_moves() {
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castling, "e1g1"), "O-O");
+        assert_eq!(san(castling, "e1c1"), "O-O-O");
+        for (text, uci) in [
+            ("O-O", "e1g1"),
+            ("0-0", "e1g1"),
+            ("O-O-O", "e1c1"),
+            ("0-0-0", "e1c1"),
+        ] {
+            assert_eq!(parse_san(castling, text).unwrap(), uci);
+        }
+
+        let passant = "4k3/8/8/4Pp2/8/8/8/4K3 w - f6 0 1";
+        assert_eq!(san(passant, "e5f6"), "exf6");
+        let mut board = from_fen(passant).unwrap();
+        let action = board.parse_uci_move("e5f6").unwrap();
+        assert_eq!(board.move_to_san_with_passant_marker(action), "exf6 e.p.");
+        for text in ["exf6", "exf6 e.p.", "ef6", "e5xf6", "Pxf6", "Pexf6"] {
+            assert_eq!(parse_san(passant, text).unwrap(), "e5f6", "{}", text);
+        }
+
+        assert_eq!(parse_san(crate::fen::STARTPOS, "Pe4").unwrap(), "e2e4");
+
+        let promotion = "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(promotion, "b7b8q"), "b8=Q+");
+        assert_eq!(san(promotion, "b7b8n"), "b8=N");
+        for (text, uci) in [
+            ("b8=Q", "b7b8q"),
+            ("b8Q", "b7b8q"),
+            ("b8=Q+", "b7b8q"),
+            ("b8=N", "b7b8n"),
+            ("Pb8=Q", "b7b8q"),
+        ] {
+            assert_eq!(parse_san(promotion, text).unwrap(), uci, "{}", text);
+        }
+        assert_eq!(
+            parse_san(promotion, "b8=K"),
+            Err(MoveParseError::InvalidFormat(String::from("b8=K")))
+        );
+        assert_eq!(
+            parse_san(promotion, "b8"),
+            Err(MoveParseError::IllegalMove(String::from("b8")))
+        );
+    }
+
+    #[test]
+    fn check_and_mate_marks() {
+        let back_rank = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        assert_eq!(san(back_rank, "a1a8"), "Ra8#");
+        assert_eq!(san(back_rank, "a1a7"), "Ra7");
+        assert_eq!(parse_san(back_rank, "Ra8#").unwrap(), "a1a8");
+        assert_eq!(parse_san(back_rank, "Ra8+!?").unwrap(), "a1a8");
+        // marks are not verified
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(parse_san(castling, "O-O+").unwrap(), "e1g1");
+    }
+
+    #[test]
+    fn san_round_trips_on_perft_positions() {
+        for fen in PERFT_POSITIONS {
+            let mut board = from_fen(fen).unwrap();
+            let mut moves = Vec::new();
+            board.generate_legal_moves(&mut moves);
+            for action in moves {
+                let mut replies = Vec::new();
+                board.make_move(action);
+                board.generate_legal_moves(&mut replies);
+                for reply in replies {
+                    let text = board.move_to_san(reply);
+                    assert_eq!(
+                        board.parse_san_move(&text),
+                        Ok(reply),
+                        "{} in {}",
+                        text,
+                        fen
+                    );
+                }
+                board.undo_move(action);
+
+                let text = board.move_to_san(action);
+                assert_eq!(
+                    board.parse_san_move(&text),
+                    Ok(action),
+                    "{} in {}",
+                    text,
+                    fen
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::bit_operations::shifts::{
+    shift_e, shift_n, shift_ne, shift_nw, shift_s, shift_se, shift_sw, shift_w,
+};
+use crate::bit_operations::{pop_count, pop_ls1b};
+use crate::board::{pieces, BoardData};
+use crate::eval::Score;
+
+/* ========================================
+*   Pawn structure. The terms are worked
+|   out with whole-board shifts, written
+*   for white moving up the board. Black's
+|   pawns are flipped with swap_bytes so
+*   the same code scores them. Pawns move
+|   rarely, so the structure is cached by
+*   pawn_key. The shield and storm terms
+|   also depend on where the king is, so
+*   they are worked out every time.
+   ======================================   */
+
+// entries in the pawn hash table, a power of two
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+const FILE_A: u64 = 0x0101010101010101;
+const RANK_1: u64 = 0xff;
+
+// by rank, from the pawn's own side
+const PASSED: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(5, 15),
+    Score::new(10, 25),
+    Score::new(20, 45),
+    Score::new(35, 75),
+    Score::new(60, 120),
+    Score::new(0, 0),
+];
+const CONNECTED: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(3, 2),
+    Score::new(5, 3),
+    Score::new(8, 6),
+    Score::new(14, 12),
+    Score::new(24, 20),
+    Score::new(40, 35),
+    Score::new(0, 0),
+];
+const DOUBLED: Score = Score::new(-10, -22);
+const ISOLATED: Score = Score::new(-8, -14);
+const BACKWARD: Score = Score::new(-8, -10);
+
+// own pawns one and two ranks in front of the king, middlegame only
+const SHIELD: [i32; 2] = [15, 8];
+// enemy pawns two, three and four ranks in front of the king
+const STORM: [i32; 3] = [-20, -12, -6];
+
+#[inline]
+fn fill_north(mut board: u64) -> u64 {
+    board |= board << 8;
+    board |= board << 16;
+    board | board << 32
+}
+
+#[inline]
+fn fill_south(mut board: u64) -> u64 {
+    board |= board >> 8;
+    board |= board >> 16;
+    board | board >> 32
+}
+
+#[inline]
+fn white_pawn_attacks(pawns: u64) -> u64 {
+    shift_ne(pawns) | shift_nw(pawns)
+}
+
+// every file with a pawn on it, filled in completely
+#[inline]
+fn files_of(pawns: u64) -> u64 {
+    fill_south(fill_north(pawns))
+}
+
+// no enemy pawn in front on the same or a neighbouring file
+fn passed_pawns(own: u64, enemy: u64) -> u64 {
+    let enemy_span = fill_south(shift_s(enemy));
+    own & !(enemy_span | shift_e(enemy_span) | shift_w(enemy_span))
+}
+
+// the pawns with another pawn of their own in front of them
+fn doubled_pawns(own: u64) -> u64 {
+    own & fill_south(shift_s(own))
+}
+
+// no pawns of their own on a neighbouring file
+fn isolated_pawns(own: u64) -> u64 {
+    let files = files_of(own);
+    own & !(shift_e(files) | shift_w(files))
+}
+
+// defended by a pawn, or standing next to one
+fn connected_pawns(own: u64) -> u64 {
+    own & (white_pawn_attacks(own) | shift_e(own) | shift_w(own))
+}
+
+// the square in front is attacked by an enemy pawn, and no pawn of our
+// own can come up next to it to cover that square
+fn backward_pawns(own: u64, enemy: u64) -> u64 {
+    let enemy_attacks = shift_se(enemy) | shift_sw(enemy);
+    let own_attack_span = fill_north(white_pawn_attacks(own));
+    shift_s(shift_n(own) & enemy_attacks & !own_attack_span) & !isolated_pawns(own)
+}
+
+// the cached part, for one side, with that side moving up the board
+fn structure(own: u64, enemy: u64) -> Score {
+    let mut score = Score::default();
+
+    let mut passed = passed_pawns(own, enemy);
+    while passed != 0 {
+        score += PASSED[(pop_ls1b(&mut passed) >> 3) as usize];
+    }
+    let mut connected = connected_pawns(own);
+    while connected != 0 {
+        score += CONNECTED[(pop_ls1b(&mut connected) >> 3) as usize];
+    }
+
+    let weak = [
+        (doubled_pawns(own), DOUBLED),
+        (isolated_pawns(own), ISOLATED),
+        (backward_pawns(own, enemy), BACKWARD),
+    ];
+    for (pawns, penalty) in weak {
+        let count = pop_count(pawns) as i32;
+        score += Score::new(penalty.mg * count, penalty.eg * count);
+    }
+    score
+}
+
+// pawns covering the king and enemy pawns advancing on it, middlegame only
+fn king_shelter(own: u64, enemy: u64, king_square: u8) -> i32 {
+    let king_file = FILE_A << (king_square & 7);
+    let files = king_file | shift_e(king_file) | shift_w(king_file);
+    let rank_ahead = |ahead: u8| {
+        let rank = (king_square >> 3) + ahead;
+        if rank < 8 {
+            files & (RANK_1 << (8 * rank))
+        } else {
+            0
+        }
+    };
+
+    let mut score = 0;
+    for (ahead, bonus) in (1..).zip(SHIELD) {
+        score += bonus * pop_count(own & rank_ahead(ahead)) as i32;
+    }
+    for (ahead, penalty) in (2..).zip(STORM) {
+        score += penalty * pop_count(enemy & rank_ahead(ahead)) as i32;
+    }
+    score
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct PawnEntry {
+    key: u64,
+    score: Score,
+}
+
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    // an empty entry has key 0 and score 0, which is also right for a board without pawns
+    pub fn new() -> PawnTable {
+        PawnTable {
+            entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoardData {
+    // pawn terms from white's point of view
+    pub fn pawn_score(&self, table: &mut PawnTable) -> Score {
+        let white = self.bitboards[pieces::WPAWN as usize];
+        let black = self.bitboards[pieces::BPAWN as usize];
+
+        let entry = &mut table.entries[self.pawn_key as usize & (PAWN_TABLE_SIZE - 1)];
+        if entry.key != self.pawn_key {
+            *entry = PawnEntry {
+                key: self.pawn_key,
+                score: structure(white, black) - structure(black.swap_bytes(), white.swap_bytes()),
+            };
+        }
+
+        let shelter = king_shelter(white, black, self.king_square(true))
+            - king_shelter(
+                black.swap_bytes(),
+                white.swap_bytes(),
+                self.king_square(false) ^ 56,
+            );
+        entry.score + Score::new(shelter, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::{from_fen, startpos};
+
+    // builds a bitboard from square names
+    fn squares(names: &[&str]) -> u64 {
+        names
+            .iter()
+            .map(|name| 1 << crate::notation::square_from_str(name).unwrap())
+            .fold(0, |board, bit| board | bit)
+    }
+
+    #[test]
+    fn structure_terms() {
+        let own = squares(&["a2", "c3", "c4", "e4", "f3", "h5"]);
+        let enemy = squares(&["d5", "e6", "h6"]);
+
+        assert_eq!(passed_pawns(own, enemy), squares(&["a2"]));
+        assert_eq!(doubled_pawns(own), squares(&["c3"]));
+        assert_eq!(isolated_pawns(own), squares(&["a2", "c3", "c4", "h5"]));
+        assert_eq!(connected_pawns(own), squares(&["e4"]));
+        // e3 can't advance past d5 and f5, and d4 is already ahead of it
+        assert_eq!(
+            backward_pawns(squares(&["d4", "e3"]), squares(&["d5", "f5"])),
+            squares(&["e3"])
+        );
+    }
+
+    #[test]
+    fn symmetric_and_cached() {
+        let mut table = PawnTable::new();
+        assert_eq!(startpos().pawn_score(&mut table), Score::default());
+
+        let fen = "4k3/1p3ppp/8/P7/3p4/8/5PPP/4K3 w - - 0 1";
+        let mirrored = "4k3/5ppp/8/3P4/p7/8/1P3PPP/4K3 b - - 0 1";
+        let score = from_fen(fen).unwrap().pawn_score(&mut table);
+        assert_eq!(from_fen(mirrored).unwrap().pawn_score(&mut table), -score);
+        // the second lookup comes from the table
+        assert_eq!(from_fen(fen).unwrap().pawn_score(&mut table), score);
+
+        table.clear();
+        assert!(table.entries.iter().all(|entry| entry.key == 0));
+        assert_eq!(from_fen(fen).unwrap().pawn_score(&mut table), score);
+    }
+
+    #[test]
+    fn pawn_key_follows_moves() {
+        let mut board = startpos();
+        let start_key = board.pawn_key;
+        for move_str in ["g1f3", "g8f6"] {
+            board.make_move(board.parse_uci_move(move_str).unwrap());
+        }
+        assert_eq!(board.pawn_key, start_key);
+        board.make_move(board.parse_uci_move("e2e4").unwrap());
+        assert_ne!(board.pawn_key, start_key);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::action::Action;
+use crate::board::BoardData;
+
+/* ========================================
+*   Perft counts the leaf nodes of the
+|   legal move tree to a fixed depth. The
+*   counts for well known positions are
+|   published, so any difference points to
+*   a bug in move generation or in
+|   make_move/undo_move.
+   ======================================   */
+
+impl BoardData {
+    pub fn perft(&mut self, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut moves = Vec::new();
+        self.generate_legal_moves(&mut moves);
+
+        let mut nodes = 0;
+        for action in moves {
+            self.make_move(action);
+            nodes += self.perft(depth - 1);
+            self.undo_move(action);
+        }
+        nodes
+    }
+
+    // node counts split up by root move, for comparing against another engine
+    pub fn perft_divide(&mut self, depth: u8) -> Vec<(Action, u64)> {
+        let mut moves = Vec::new();
+        self.generate_legal_moves(&mut moves);
+
+        let mut divided = Vec::with_capacity(moves.len());
+        for action in moves {
+            self.make_move(action);
+            let nodes = if depth > 1 { self.perft(depth - 1) } else { 1 };
+            self.undo_move(action);
+            divided.push((action, nodes));
+        }
+        divided
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::from_fen;
+    use crate::testing::{
+        KIWIPETE, POSITION_3, POSITION_4, POSITION_4_MIRRORED, POSITION_5, POSITION_6, STARTPOS,
+    };
+
+    // the expected node counts are from https://www.chessprogramming.org/Perft_Results
+    fn check_perft(fen: &str, expected: &[u64]) {
+        let mut board = from_fen(fen).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                board.perft(depth as u8 + 1),
+                *nodes,
+                "perft({}) of {}",
+                depth + 1,
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn perft_startpos() {
+        check_perft(STARTPOS, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        check_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        check_perft(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        check_perft(POSITION_4, &[6, 264, 9467, 422333]);
+    }
+
+    #[test]
+    fn perft_position_4_mirrored() {
+        check_perft(POSITION_4_MIRRORED, &[6, 264, 9467, 422333]);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        check_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        check_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn perft_divide_sums_to_perft() {
+        let mut board = from_fen(KIWIPETE).unwrap();
+        let divided = board.perft_divide(2);
+        assert_eq!(divided.len(), 48);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+    }
+
+    #[test]
+    fn perft_restores_board() {
+        let mut board = from_fen(KIWIPETE).unwrap();
+        let key = board.zobrist_key;
+        let bitboards = board.bitboards;
+        board.perft(3);
+        assert_eq!(board.zobrist_key, key);
+        assert_eq!(board.bitboards, bitboards);
+        assert_eq!(board.to_fen(), from_fen(KIWIPETE).unwrap().to_fen());
+    }
+}
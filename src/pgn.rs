@@ -0,0 +1,749 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::action::Action;
+use crate::board::BoardData;
+use crate::fen::{self, FenError};
+use crate::notation::MoveParseError;
+
+/* ========================================
+*   PGN reading. A file may hold any number
+|   of games, each a set of tag pairs then
+*   movetext. Only the mainline is kept:
+|   variations are skipped, comments and
+*   NAGs are attached to the move before
+|   them. Moves stay as SAN text until the
+*   game is replayed onto a board.
+   ======================================   */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    UnterminatedTag,
+    InvalidTag(String),
+    UnterminatedComment,
+    InvalidComment(String),
+    UnbalancedVariation,
+    InvalidFen(FenError),
+    IllegalMove { ply: usize, error: MoveParseError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::UnterminatedTag => write!(f, "unterminated tag pair"),
+            PgnError::InvalidTag(tag) => write!(f, "invalid tag pair: {}", tag),
+            PgnError::UnterminatedComment => write!(f, "unterminated comment"),
+            PgnError::InvalidComment(comment) => {
+                write!(f, "comment can't contain a closing brace: {}", comment)
+            }
+            PgnError::UnbalancedVariation => write!(f, "unbalanced variation"),
+            PgnError::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+            PgnError::IllegalMove { ply, error } => write!(f, "ply {}: {}", ply + 1, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    // comments that come before the first move
+    pub leading_comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+    // the termination marker at the end of the movetext
+    pub result: Option<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.leading_comments.is_empty()
+            && self.moves.is_empty()
+            && self.result.is_none()
+    }
+
+    pub fn starting_position(&self) -> Result<BoardData, PgnError> {
+        match self.tag("FEN") {
+            Some(fen_string) if self.tag("SetUp") != Some("0") => {
+                fen::from_fen(fen_string).map_err(PgnError::InvalidFen)
+            }
+            _ => Ok(fen::startpos()),
+        }
+    }
+
+    // plays the mainline from the starting position, yielding the position
+    // before each move along with the move itself. the yielded positions
+    // carry no move history, so they can't undo moves or see repetitions
+    pub fn replay(&self) -> Result<Replay<'_>, PgnError> {
+        Ok(Replay {
+            game: self,
+            board: self.starting_position()?,
+            ply: 0,
+            failed: false,
+        })
+    }
+}
+
+pub struct PgnPly {
+    pub ply: usize,
+    pub position: BoardData, // without prev_states and key_history
+    pub action: Action,
+}
+
+pub struct Replay<'a> {
+    game: &'a PgnGame,
+    board: BoardData,
+    ply: usize,
+    failed: bool,
+}
+
+impl Replay<'_> {
+    // the position after every move yielded so far
+    pub fn board(&self) -> &BoardData {
+        &self.board
+    }
+}
+
+impl Iterator for Replay<'_> {
+    type Item = Result<PgnPly, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let pgn_move = self.game.moves.get(self.ply)?;
+        let ply = self.ply;
+        match self.board.parse_san_move(&pgn_move.san) {
+            Ok(action) => {
+                // cloning the history would make every ply cost as much as the game so far
+                let prev_states = std::mem::take(&mut self.board.prev_states);
+                let key_history = std::mem::take(&mut self.board.key_history);
+                let position = self.board.clone();
+                self.board.prev_states = prev_states;
+                self.board.key_history = key_history;
+
+                self.board.make_move(action);
+                self.ply += 1;
+                Some(Ok(PgnPly {
+                    ply,
+                    position,
+                    action,
+                }))
+            }
+            Err(error) => {
+                self.failed = true;
+                Some(Err(PgnError::IllegalMove { ply, error }))
+            }
+        }
+    }
+}
+
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::default();
+    let mut chars = text.char_indices().peekable();
+    let mut line_start = true;
+
+    while let Some(&(_, c)) = chars.peek() {
+        if c == '\n' {
+            line_start = true;
+            chars.next();
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let at_line_start = line_start;
+        line_start = false;
+
+        match c {
+            // escape mechanism: the rest of the line is ignored
+            '%' if at_line_start => {
+                skip_line(&mut chars);
+                line_start = true;
+            }
+            ';' => {
+                skip_line(&mut chars);
+                line_start = true;
+            }
+            '[' => {
+                // a tag section after movetext starts a new game
+                if !game.moves.is_empty() || game.result.is_some() {
+                    games.push(std::mem::take(&mut game));
+                }
+                chars.next();
+                game.tags.push(read_tag(&mut chars)?);
+            }
+            '{' => {
+                chars.next();
+                let comment = read_comment(&mut chars)?;
+                match game.moves.last_mut() {
+                    Some(last) => last.comments.push(comment),
+                    None => game.leading_comments.push(comment),
+                }
+            }
+            '(' => {
+                chars.next();
+                skip_variation(&mut chars)?;
+            }
+            ')' => return Err(PgnError::UnbalancedVariation),
+            '$' => {
+                chars.next();
+                let token = read_token(text, &mut chars);
+                if let (Ok(nag), Some(last)) = (token.parse(), game.moves.last_mut()) {
+                    last.nags.push(nag);
+                }
+            }
+            _ => {
+                let token = read_token(text, &mut chars);
+                if token.is_empty() {
+                    // a stray delimiter such as '}' or ']'
+                    chars.next();
+                    continue;
+                }
+                // "exd6 e.p." is one move, and the marker tells us nothing the board doesn't
+                if token == "e.p." {
+                    continue;
+                }
+                if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    game.result = Some(token.to_string());
+                    games.push(std::mem::take(&mut game));
+                    continue;
+                }
+                // move numbers may be attached to the move: "12.Nf3", "12...Nf3"
+                let after_digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                let san = if after_digits.starts_with('.') {
+                    after_digits.trim_start_matches('.')
+                } else {
+                    token
+                };
+                if !san.is_empty() {
+                    game.moves.push(PgnMove {
+                        san: san.to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    if !game.is_empty() {
+        games.push(game);
+    }
+    Ok(games)
+}
+
+fn skip_line(chars: &mut Peekable<CharIndices>) {
+    for (_, c) in chars.by_ref() {
+        if c == '\n' {
+            break;
+        }
+    }
+}
+
+// reads until whitespace or a delimiter
+fn read_token<'a>(text: &'a str, chars: &mut Peekable<CharIndices>) -> &'a str {
+    let start = match chars.peek() {
+        Some(&(idx, _)) => idx,
+        None => return "",
+    };
+    let mut end = text.len();
+    while let Some(&(idx, c)) = chars.peek() {
+        if c.is_whitespace() || "{}()[];$".contains(c) {
+            end = idx;
+            break;
+        }
+        chars.next();
+    }
+    &text[start..end]
+}
+
+// [Name "value"], with the opening bracket already consumed
+fn read_tag(chars: &mut Peekable<CharIndices>) -> Result<(String, String), PgnError> {
+    let mut name = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut closed_value = false;
+
+    while let Some((_, c)) = chars.next() {
+        if in_value {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => value.push(escaped),
+                    None => return Err(PgnError::UnterminatedTag),
+                },
+                '"' => {
+                    in_value = false;
+                    closed_value = true;
+                }
+                _ => value.push(c),
+            }
+            continue;
+        }
+        match c {
+            ']' => {
+                if name.is_empty() || !closed_value {
+                    return Err(PgnError::InvalidTag(name));
+                }
+                return Ok((name, value));
+            }
+            '"' if !closed_value => in_value = true,
+            '\n' => return Err(PgnError::UnterminatedTag),
+            c if c.is_whitespace() => (),
+            c if !closed_value && (c.is_alphanumeric() || c == '_') => name.push(c),
+            _ => return Err(PgnError::InvalidTag(name)),
+        }
+    }
+    Err(PgnError::UnterminatedTag)
+}
+
+// {comment}, with the opening brace already consumed. the text is kept
+// exactly as written, spacing and line breaks included
+fn read_comment(chars: &mut Peekable<CharIndices>) -> Result<String, PgnError> {
+    let mut comment = String::new();
+    for (_, c) in chars.by_ref() {
+        if c == '}' {
+            return Ok(comment);
+        }
+        comment.push(c);
+    }
+    Err(PgnError::UnterminatedComment)
+}
+
+// skips a possibly nested variation, with the opening paren already consumed
+fn skip_variation(chars: &mut Peekable<CharIndices>) -> Result<(), PgnError> {
+    let mut depth = 1;
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            '{' => {
+                read_comment(chars)?;
+            }
+            ';' => skip_line(chars),
+            _ => (),
+        }
+    }
+    Err(PgnError::UnbalancedVariation)
+}
+
+/* ========================================
+*   PGN writing, in export format: the
+|   seven tag roster comes first in its
+*   fixed order, movetext is SAN with move
+|   numbers, and lines are wrapped between
+*   tokens to fit in 80 columns. Comments
+|   are written exactly as given.
+   ======================================   */
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+const MAX_LINE_LENGTH: usize = 79;
+
+// engine evaluation comment in the common "{+0.35/12}" form, score in centipawns
+pub fn eval_comment(score: i32, depth: u8) -> String {
+    format!("{:+.2}/{}", score as f64 / 100.0, depth)
+}
+
+impl PgnGame {
+    // renders the moves as SAN. SetUp and FEN tags are added if the game
+    // doesn't start from the standard position
+    pub fn from_actions(start: &BoardData, actions: &[Action]) -> PgnGame {
+        let mut game = PgnGame::default();
+        let start_fen = start.to_fen();
+        if start_fen != fen::startpos().to_fen() {
+            game.tags.push((String::from("SetUp"), String::from("1")));
+            game.tags.push((String::from("FEN"), start_fen));
+        }
+
+        let mut board = start.clone();
+        for action in actions {
+            game.moves.push(PgnMove {
+                san: board.move_to_san(*action),
+                ..Default::default()
+            });
+            board.make_move(*action);
+        }
+        game
+    }
+
+    pub fn to_pgn(&self) -> Result<String, PgnError> {
+        let result = self.result.as_deref().or(self.tag("Result")).unwrap_or("*");
+
+        let mut pgn = String::new();
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => result,
+                _ => self.tag(name).unwrap_or(default),
+            };
+            pgn += &format_tag(name, value);
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER
+                .iter()
+                .any(|(roster_name, _)| roster_name == name)
+            {
+                pgn += &format_tag(name, value);
+            }
+        }
+        pgn.push('\n');
+
+        let start = self.starting_position()?;
+        let mut white_to_move = start.to_move;
+        let mut move_number = start.fullmove_number;
+        let mut tokens: Vec<String> = Vec::new();
+        for comment in &self.leading_comments {
+            tokens.push(format_comment(comment)?);
+        }
+        // black's move needs its number if it is the first move or follows a comment
+        let mut needs_number = true;
+        for pgn_move in &self.moves {
+            if white_to_move {
+                tokens.push(format!("{}.", move_number));
+            } else if needs_number {
+                tokens.push(format!("{}...", move_number));
+            }
+            tokens.push(pgn_move.san.clone());
+            needs_number = false;
+            for nag in &pgn_move.nags {
+                tokens.push(format!("${}", nag));
+            }
+            for comment in &pgn_move.comments {
+                tokens.push(format_comment(comment)?);
+                needs_number = true;
+            }
+            if !white_to_move {
+                move_number += 1;
+            }
+            white_to_move = !white_to_move;
+        }
+        tokens.push(result.to_string());
+
+        pgn += &wrap_tokens(&tokens);
+        pgn.push('\n');
+        Ok(pgn)
+    }
+}
+
+// caller tags replace any tag of the same name. FEN and SetUp come from
+// the start position alone, so the caller's are ignored
+pub fn write_pgn(
+    start: &BoardData,
+    actions: &[Action],
+    tags: &[(String, String)],
+    comments: &[Option<String>],
+) -> Result<String, PgnError> {
+    let mut game = PgnGame::from_actions(start, actions);
+    for (name, value) in tags {
+        if name == "FEN" || name == "SetUp" {
+            continue;
+        }
+        match game.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(tag) => tag.1 = value.clone(),
+            None => game.tags.push((name.clone(), value.clone())),
+        }
+    }
+    for (pgn_move, comment) in game.moves.iter_mut().zip(comments) {
+        if let Some(comment) = comment {
+            pgn_move.comments.push(comment.clone());
+        }
+    }
+    game.to_pgn()
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[{} \"{}\"]\n", name, escaped)
+}
+
+// the text is written as it is. PGN has no escape for a closing brace,
+// which would end the comment early, so one is an error
+fn format_comment(comment: &str) -> Result<String, PgnError> {
+    if comment.contains('}') {
+        return Err(PgnError::InvalidComment(comment.to_string()));
+    }
+    Ok(format!("{{{}}}", comment))
+}
+
+// joins tokens with spaces, breaking lines between tokens only. a comment
+// may run past the line length or hold line breaks of its own
+fn wrap_tokens(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        let first_line = token.split('\n').next().unwrap_or_default();
+        if line_length > 0 && line_length + 1 + first_line.len() > MAX_LINE_LENGTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text += token;
+        line_length = match token.rfind('\n') {
+            Some(newline) => token.len() - newline - 1,
+            None => line_length + token.len(),
+        };
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Move;
+
+    fn sans(game: &PgnGame) -> Vec<&str> {
+        game.moves
+            .iter()
+            .map(|pgn_move| pgn_move.san.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn reads_several_games() {
+        let text = "[Event \"first\"]\n[White \"A\"]\n\n1. e4 e5 2. Nf3 1-0\n\n\
+                    [Event \"second\"]\n\n1.d4 d5 2.c4 *\n";
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("first"));
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(sans(&games[0]), ["e4", "e5", "Nf3"]);
+        assert_eq!(games[0].result.as_deref(), Some("1-0"));
+        assert_eq!(games[1].tag("Event"), Some("second"));
+        assert_eq!(sans(&games[1]), ["d4", "d5", "c4"]);
+        assert_eq!(games[1].result.as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn skips_variations_and_keeps_annotations() {
+        let text = "{opening} 1. e4 $1 {best by test} (1. d4 d5 (1... Nf6 {indian}) 2. c4)\n\
+                    1... e5 $2 $14 ; the rest of this line is a comment (\n\
+                    % so is this line {\n\
+                    2. Nf3 *";
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.leading_comments, ["opening"]);
+        assert_eq!(sans(game), ["e4", "e5", "Nf3"]);
+        assert_eq!(game.moves[0].nags, [1]);
+        assert_eq!(game.moves[0].comments, ["best by test"]);
+        assert_eq!(game.moves[1].nags, [2, 14]);
+
+        let games = parse_pgn("1. e4 { two  spaces,\n a { and a line break } *").unwrap();
+        assert_eq!(
+            games[0].moves[0].comments,
+            [" two  spaces,\n a { and a line break "]
+        );
+
+        assert_eq!(
+            parse_pgn("1. e4 (1. d4 *"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(
+            parse_pgn("1. e4 {never closed"),
+            Err(PgnError::UnterminatedComment)
+        );
+    }
+
+    #[test]
+    fn escaped_tag_values() {
+        let games = parse_pgn("[Event \"the \\\"big\\\" one \\\\ 2\"]\n*").unwrap();
+        assert_eq!(games[0].tag("Event"), Some("the \"big\" one \\ 2"));
+        assert_eq!(
+            parse_pgn("[Event \"open\n*"),
+            Err(PgnError::UnterminatedTag)
+        );
+    }
+
+    #[test]
+    fn replays_from_setup_position() {
+        let text = "[SetUp \"1\"]\n\
+                    [FEN \"rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3\"]\n\n\
+                    3. exf6 e.p. exf6 4. Nf3 *";
+        let game = &parse_pgn(text).unwrap()[0];
+        assert_eq!(sans(game), ["exf6", "exf6", "Nf3"]);
+
+        let mut replay = game.replay().unwrap();
+        let plies: Vec<PgnPly> = replay.by_ref().map(|ply| ply.unwrap()).collect();
+        assert_eq!(plies.len(), 3);
+        assert_eq!(plies[0].action.to_uci(), "e5f6");
+        assert_eq!(plies[0].position.fullmove_number, 3);
+        assert_eq!(plies[2].ply, 2);
+        assert!(plies.iter().all(|ply| ply.position.prev_states.is_empty()));
+        assert_eq!(
+            replay.board().to_fen(),
+            "rnbqkbnr/ppp3pp/5p2/3p4/8/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 4"
+        );
+    }
+
+    #[test]
+    fn illegal_move_reports_its_ply() {
+        let game = &parse_pgn("1. e4 e5 2. Ke3 Nc6 *").unwrap()[0];
+        let results: Vec<_> = game.replay().unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(|result| result.is_ok()));
+        assert!(matches!(
+            results[2],
+            Err(PgnError::IllegalMove {
+                ply: 2,
+                error: MoveParseError::IllegalMove(_)
+            })
+        ));
+
+        let bad_fen = &parse_pgn("[SetUp \"1\"]\n[FEN \"8/8 w - -\"]\n*").unwrap()[0];
+        assert!(matches!(bad_fen.replay(), Err(PgnError::InvalidFen(_))));
+    }
+
+    fn tag(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    fn play(start: &BoardData, uci_moves: &[&str]) -> Vec<Action> {
+        let mut board = start.clone();
+        uci_moves
+            .iter()
+            .map(|move_str| {
+                let action = board.parse_uci_move(move_str).unwrap();
+                board.make_move(action);
+                action
+            })
+            .collect()
+    }
+
+    #[test]
+    fn written_games_read_back() {
+        let start = fen::startpos();
+        let moves = [
+            "e2e4", "d7d5", "e4d5", "g8f6", "f1b5", "c7c6", "d5c6", "d8a5", "c6b7", "a5b5",
+            "b7a8q", "b5b2", "g1f3", "h7h6", "e1g1",
+        ];
+        let actions = play(&start, &moves);
+        let tags = [
+            tag("Event", "the \"big\" one \\ 2"),
+            tag("White", "engine"),
+            tag("Result", "1-0"),
+            tag("Annotator", "test"),
+        ];
+        let pgn = write_pgn(&start, &actions, &tags, &[]).unwrap();
+        assert!(pgn.starts_with("[Event \"the \\\"big\\\" one \\\\ 2\"]\n[Site \"?\"]\n"));
+        assert!(pgn.contains("bxa8=Q"));
+        assert!(pgn.trim_end().ends_with("O-O 1-0"));
+        assert!(!pgn.contains("FEN"));
+
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        for (name, value) in &tags {
+            assert_eq!(game.tag(name), Some(value.as_str()));
+        }
+        let replayed: Vec<Action> = game
+            .replay()
+            .unwrap()
+            .map(|ply| ply.unwrap().action)
+            .collect();
+        assert_eq!(replayed, actions);
+    }
+
+    #[test]
+    fn setup_positions_and_move_numbers() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 12";
+        let start = fen::from_fen(fen).unwrap();
+        let actions = play(&start, &["e7e5", "g1f3", "b8c6", "f1c4"]);
+        // the start position wins over caller FEN and SetUp tags, and other tags are replaced
+        let tags = [
+            tag("FEN", "x"),
+            tag("SetUp", "0"),
+            tag("Event", "first"),
+            tag("Event", "second"),
+        ];
+        let comments = [None, Some(String::from("+0.35/12")), None];
+        let pgn = write_pgn(&start, &actions, &tags, &comments).unwrap();
+
+        assert_eq!(pgn.matches("[Event ").count(), 1);
+        assert!(pgn.contains("[Event \"second\"]\n"));
+        assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", fen)));
+        assert!(pgn.contains("\n12... e5 13. Nf3 {+0.35/12} 13... Nc6 14. Bc4 *"));
+
+        let game = &parse_pgn(&pgn).unwrap()[0];
+        let replayed: Vec<Action> = game
+            .replay()
+            .unwrap()
+            .map(|ply| ply.unwrap().action)
+            .collect();
+        assert_eq!(replayed, actions);
+    }
+
+    #[test]
+    fn comments_are_written_as_they_are() {
+        let start = fen::startpos();
+        let actions = play(&start, &["e2e4", "e7e5", "g1f3"]);
+        let comments = [
+            Some(String::from("  two  spaces  ")),
+            Some(String::from("a line\n\nand {another")),
+            None,
+        ];
+        let pgn = write_pgn(&start, &actions, &[], &comments).unwrap();
+        let game = &parse_pgn(&pgn).unwrap()[0];
+        assert_eq!(game.moves[0].comments, ["  two  spaces  "]);
+        assert_eq!(game.moves[1].comments, ["a line\n\nand {another"]);
+        assert_eq!(sans(game), ["e4", "e5", "Nf3"]);
+
+        let closing = [Some(String::from("a } b"))];
+        assert_eq!(
+            write_pgn(&start, &actions, &[], &closing),
+            Err(PgnError::InvalidComment(String::from("a } b")))
+        );
+    }
+
+    #[test]
+    fn lines_are_wrapped() {
+        let start = fen::startpos();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let moves: Vec<&str> = shuffle.iter().cycle().take(60).copied().collect();
+        let actions = play(&start, &moves);
+        let comments: Vec<Option<String>> = (0..60)
+            .map(|ply| Some(eval_comment(ply * 7 - 200, 10)))
+            .collect();
+        let pgn = write_pgn(&start, &actions, &[], &comments).unwrap();
+
+        let movetext: Vec<&str> = pgn
+            .lines()
+            .skip_while(|line| line.starts_with('['))
+            .collect();
+        assert!(movetext.len() > 5);
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(pgn.contains("{-2.00/10}"));
+        assert_eq!(parse_pgn(&pgn).unwrap()[0].moves.len(), 60);
+    }
+}
@@ -0,0 +1,569 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::action::{actions, Action, Move};
+use crate::board::{pieces, BoardData};
+use crate::pawns::PawnTable;
+use crate::tt::{Bound, TranspositionTable};
+
+/* ========================================
+*   Negamax alpha-beta search with
+|   principal variation search, run by
+*   iterative deepening. Each iteration
+|   searches the previous best line first,
+*   so a search cut off by the clock still
+|   has the best move of the last finished
+*   depth to fall back on.
+   ======================================   */
+
+pub const INFINITY: i32 = 32000;
+pub const MATE: i32 = 31000;
+pub const MAX_PLY: usize = 128;
+// scores beyond this are mates, counted in plies from the root
+pub const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+// how many nodes between looks at the clock and the stop flag
+const CHECK_INTERVAL: u64 = 2047;
+
+// rough piece values for move ordering and delta pruning, by piece type:
+// king, queen, bishop, knight, rook, pawn
+const PIECE_VALUES: [i32; 6] = [0, 900, 330, 320, 500, 100];
+
+// a capture that can't bring the score within this of alpha isn't searched
+const DELTA_MARGIN: i32 = 200;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+// progress after each finished iteration
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub score: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub hashfull: usize,
+    pub pv: Vec<Action>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Action>,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+    pub pv: Vec<Action>,
+}
+
+pub struct Search<'a> {
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    tt: &'a mut TranspositionTable,
+    // kept between searches, like the transposition table
+    pawn_table: &'a mut PawnTable,
+    start: Instant,
+    nodes: u64,
+    stopped: bool,
+    // best line from the last finished iteration
+    prev_pv: Vec<Action>,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(
+        limits: SearchLimits,
+        stop: Arc<AtomicBool>,
+        tt: &'a mut TranspositionTable,
+        pawn_table: &'a mut PawnTable,
+    ) -> Search<'a> {
+        Search {
+            limits,
+            stop,
+            tt,
+            pawn_table,
+            start: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            prev_pv: Vec::new(),
+        }
+    }
+
+    pub fn run(
+        &mut self,
+        board: &mut BoardData,
+        mut report: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.stopped = false;
+        self.prev_pv.clear();
+        self.tt.new_search();
+
+        let mut root_moves = Vec::new();
+        board.generate_legal_moves(&mut root_moves);
+        let mut result = SearchResult {
+            best_move: root_moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        if root_moves.is_empty() {
+            result.score = if board.in_check() { -MATE } else { 0 };
+            return result;
+        }
+
+        let max_depth = self
+            .limits
+            .depth
+            .unwrap_or(MAX_PLY as u8 - 1)
+            .min(MAX_PLY as u8 - 1);
+        for depth in 1..=max_depth {
+            let mut pv = Vec::new();
+            let score = self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+
+            // an unfinished iteration's result can't be trusted
+            if self.stopped {
+                break;
+            }
+            result.best_move = pv.first().copied().or(result.best_move);
+            result.score = score;
+            result.depth = depth;
+            result.pv = pv.clone();
+            self.prev_pv = pv;
+
+            report(&SearchInfo {
+                depth,
+                score,
+                nodes: self.nodes,
+                elapsed: self.start.elapsed(),
+                hashfull: self.tt.hashfull(),
+                pv: result.pv.clone(),
+            });
+
+            // mate found, deeper searches can't improve on it
+            if score.abs() >= MATE_BOUND && MATE - score.abs() <= depth as i32 {
+                break;
+            }
+            // the next iteration takes longer than all the ones before it
+            if let Some(time) = self.limits.time {
+                if self.start.elapsed() * 2 > time {
+                    break;
+                }
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut BoardData,
+        depth: u8,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
+        pv: &mut Vec<Action>,
+    ) -> i32 {
+        pv.clear();
+        self.nodes += 1;
+        if self.nodes & CHECK_INTERVAL == 0 {
+            self.check_limits();
+        }
+        if self.stopped {
+            return 0;
+        }
+
+        if ply > 0 {
+            if is_draw(board, ply) {
+                return 0;
+            }
+            // no line from here can beat a mate that was already found closer to the root
+            alpha = alpha.max(-MATE + ply as i32);
+            beta = beta.min(MATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let in_check = board.in_check();
+        // don't stop the search in the middle of a check
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let tt_entry = self.tt.probe(board.zobrist_key);
+        if let Some(entry) = tt_entry {
+            // only null window nodes are cut, so that the pv isn't cut short
+            if ply > 0 && beta - alpha == 1 && entry.depth >= depth {
+                let score = entry.score(ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        let mut moves = Vec::new();
+        board.generate_legal_moves(&mut moves);
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        let hash_move = tt_entry.and_then(|entry| entry.best_move);
+        order_by_captures(
+            board,
+            &mut moves,
+            hash_move.or(self.prev_pv.get(ply).copied()),
+        );
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut child_pv = Vec::new();
+        for (i, action) in moves.into_iter().enumerate() {
+            board.make_move(action);
+            let score = if i == 0 {
+                -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv)
+            } else {
+                // prove the move is worse with a null window, search again if it isn't
+                let score =
+                    -self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha, &mut child_pv);
+                if score > alpha && score < beta {
+                    -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv)
+                } else {
+                    score
+                }
+            };
+            board.undo_move(action);
+
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    best_move = Some(action);
+                    pv.clear();
+                    pv.push(action);
+                    pv.extend_from_slice(&child_pv);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt
+            .store(board.zobrist_key, depth, bound, best, best_move, ply);
+        best
+    }
+
+    /* ========================================
+    *   Quiescence search keeps searching
+    |   captures and promotions past the end of
+    *   the main search, so that a position is
+    |   never scored in the middle of an
+    *   exchange. The side to move may "stand
+    |   pat" on the static eval instead of
+    *   capturing, unless it is in check.
+       ======================================   */
+    fn quiescence(&mut self, board: &mut BoardData, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes & CHECK_INTERVAL == 0 {
+            self.check_limits();
+        }
+        if self.stopped {
+            return 0;
+        }
+
+        let in_check = board.in_check();
+        if ply >= MAX_PLY - 1 {
+            return board.evaluate(self.pawn_table);
+        }
+
+        let mut moves = Vec::new();
+        let stand_pat = if in_check {
+            // every evasion has to be looked at
+            board.generate_legal_moves(&mut moves);
+            if moves.is_empty() {
+                return -MATE + ply as i32;
+            }
+            -INFINITY
+        } else {
+            let stand_pat = board.evaluate(self.pawn_table);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            board.generate_legal_captures(&mut moves);
+            stand_pat
+        };
+        order_by_captures(board, &mut moves, None);
+
+        let mut best = stand_pat;
+        for action in moves {
+            let is_promotion = action.move_type() == actions::PROMOTION;
+            if !in_check {
+                // underpromotions almost never matter this deep
+                if is_promotion && action.promote_to() != actions::PR_QUEEN {
+                    continue;
+                }
+                // even winning the piece for free wouldn't reach alpha
+                let gain = captured_value(board, action)
+                    + if is_promotion {
+                        PIECE_VALUES[pieces::WQUEEN as usize]
+                    } else {
+                        0
+                    };
+                if stand_pat + gain + DELTA_MARGIN < alpha {
+                    continue;
+                }
+            }
+
+            let fast_capture = action.move_type() == actions::NORMAL
+                && board.mailbox[action.move_to() as usize] != pieces::NOPIECE;
+            if fast_capture {
+                board.do_capture(action);
+            } else {
+                board.make_move(action);
+            }
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            if fast_capture {
+                board.undo_capture(action);
+            } else {
+                board.undo_move(action);
+            }
+
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn check_limits(&mut self) {
+        if self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if let Some(time) = self.limits.time {
+            if self.start.elapsed() >= time {
+                self.stopped = true;
+            }
+        }
+        if let Some(nodes) = self.limits.nodes {
+            if self.nodes >= nodes {
+                self.stopped = true;
+            }
+        }
+    }
+}
+
+// the best move from before first, then captures by most valuable victim and least
+// valuable attacker, then everything else
+fn order_by_captures(board: &BoardData, moves: &mut [Action], best_move: Option<Action>) {
+    moves.sort_by_cached_key(|action| {
+        if Some(*action) == best_move {
+            return i32::MIN;
+        }
+        let attacker = board.mailbox[action.move_from() as usize];
+        let mut score = 0;
+        let victim_value = captured_value(board, *action);
+        if victim_value != 0 {
+            score += 10 * victim_value - PIECE_VALUES[(attacker % 6) as usize];
+        }
+        if action.move_type() == actions::PROMOTION && action.promote_to() == actions::PR_QUEEN {
+            score += PIECE_VALUES[pieces::WQUEEN as usize];
+        }
+        -score
+    });
+}
+
+#[inline]
+fn captured_value(board: &BoardData, action: Action) -> i32 {
+    if action.move_type() == actions::PASSANT {
+        return PIECE_VALUES[pieces::WPAWN as usize];
+    }
+    match board.mailbox[action.move_to() as usize] {
+        pieces::NOPIECE => 0,
+        victim => PIECE_VALUES[(victim % 6) as usize],
+    }
+}
+
+// plies until mate for a mate score, negative when the side to move gets mated
+pub fn mate_in_plies(score: i32) -> Option<i32> {
+    if score >= MATE_BOUND {
+        Some(MATE - score)
+    } else if score <= -MATE_BOUND {
+        Some(-(MATE + score))
+    } else {
+        None
+    }
+}
+
+fn is_draw(board: &BoardData, ply: usize) -> bool {
+    board.is_fifty_move_draw()
+        || board.is_search_repetition(ply)
+        || board.is_insufficient_material()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::from_fen;
+    use crate::testing::KIWIPETE;
+
+    fn search(fen: &str, depth: u8) -> SearchResult {
+        let mut board = from_fen(fen).unwrap();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+        let mut tt = TranspositionTable::new(1);
+        let mut pawns = PawnTable::new();
+        Search::new(
+            limits,
+            Arc::new(AtomicBool::new(false)),
+            &mut tt,
+            &mut pawns,
+        )
+        .run(&mut board, |_| ())
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!(result.best_move.unwrap().to_uci(), "a1a8");
+        assert_eq!(mate_in_plies(result.score), Some(1));
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        // 1. Kb6 Kb8 2. Rh8#
+        let result = search("k7/8/2K5/8/8/8/8/7R w - - 0 1", 4);
+        assert_eq!(mate_in_plies(result.score), Some(3));
+    }
+
+    #[test]
+    fn wins_hanging_queen() {
+        let result = search("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 2);
+        assert_eq!(result.best_move.unwrap().to_uci(), "d1d5");
+    }
+
+    #[test]
+    fn leaves_defended_pawn_alone() {
+        // Qxd5 wins a pawn at depth 1, but quiescence sees exd5
+        let result = search("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 1);
+        assert_ne!(result.best_move.unwrap().to_uci(), "d1d5");
+    }
+
+    #[test]
+    fn insufficient_material_is_a_draw() {
+        let result = search("8/8/4k3/8/8/3NK3/8/8 w - - 0 1", 4);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn mated_side_has_no_move() {
+        let result = search("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", 3);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE);
+    }
+
+    // the score and node count of a quiescence search on its own
+    fn quiescence(fen: &str, alpha: i32, beta: i32) -> (i32, u64) {
+        let mut board = from_fen(fen).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let mut pawns = PawnTable::new();
+        let mut search = Search::new(
+            SearchLimits::default(),
+            Arc::new(AtomicBool::new(false)),
+            &mut tt,
+            &mut pawns,
+        );
+        let score = search.quiescence(&mut board, 0, alpha, beta);
+        assert_eq!(board.to_fen(), fen);
+        (score, search.nodes)
+    }
+
+    fn static_eval(fen: &str) -> i32 {
+        from_fen(fen).unwrap().evaluate(&mut PawnTable::new())
+    }
+
+    #[test]
+    fn quiescence_stands_pat() {
+        // nothing to capture, so the static eval is the answer
+        let quiet = "4k3/8/8/3p4/8/8/4P3/4K3 w - - 0 1";
+        assert_eq!(
+            quiescence(quiet, -INFINITY, INFINITY),
+            (static_eval(quiet), 1)
+        );
+
+        // the static eval is already good enough, so the capture isn't looked at
+        let capture = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1";
+        let eval = static_eval(capture);
+        assert_eq!(quiescence(capture, -INFINITY, eval), (eval, 1));
+        let (score, nodes) = quiescence(capture, -INFINITY, INFINITY);
+        assert!(score > eval);
+        assert_eq!(nodes, 2);
+    }
+
+    #[test]
+    fn quiescence_delta_prunes() {
+        // a pawn plus the margin can't reach alpha, so exd5 is skipped
+        let capture = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1";
+        let eval = static_eval(capture);
+        let alpha = eval + PIECE_VALUES[pieces::WPAWN as usize] + DELTA_MARGIN + 1;
+        assert_eq!(quiescence(capture, alpha, INFINITY), (eval, 1));
+        assert_eq!(quiescence(capture, alpha - 2, INFINITY).1, 2);
+    }
+
+    #[test]
+    fn quiescence_sees_mate_when_in_check() {
+        let mated = "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1";
+        assert_eq!(quiescence(mated, -INFINITY, INFINITY).0, -MATE);
+    }
+
+    #[test]
+    fn search_restores_board() {
+        let mut board = from_fen(KIWIPETE).unwrap();
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+        let mut tt = TranspositionTable::new(1);
+        let mut pawns = PawnTable::new();
+        Search::new(
+            limits,
+            Arc::new(AtomicBool::new(false)),
+            &mut tt,
+            &mut pawns,
+        )
+        .run(&mut board, |_| ());
+        assert_eq!(board.to_fen(), KIWIPETE);
+        assert!(board.prev_states.is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use crate::board::BoardData;
+
+/* ========================================
+*   Whether the game is over, and why.
+|   Checkmate and stalemate come first, a
+*   mate on the move that reaches a draw
+|   rule still wins. Fivefold repetition,
+*   the seventy-five move rule and dead
+|   positions end the game on their own,
+*   threefold repetition and the fifty move
+|   rule only when a player claims them.
+   ======================================   */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate(bool), // the winner, true means white
+    Stalemate,
+    InsufficientMaterial,
+    FivefoldRepetition,
+    SeventyFiveMove,
+    ThreefoldRepetition,
+    FiftyMove,
+}
+
+impl GameStatus {
+    pub fn is_over(&self) -> bool {
+        *self != GameStatus::Ongoing
+    }
+
+    // threefold repetition and the fifty move rule only end the game if claimed
+    pub fn is_claimable(&self) -> bool {
+        matches!(
+            self,
+            GameStatus::ThreefoldRepetition | GameStatus::FiftyMove
+        )
+    }
+
+    // as written in the PGN Result tag
+    pub fn result(&self) -> &'static str {
+        match self {
+            GameStatus::Ongoing => "*",
+            GameStatus::Checkmate(true) => "1-0",
+            GameStatus::Checkmate(false) => "0-1",
+            _ => "1/2-1/2",
+        }
+    }
+}
+
+impl fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameStatus::Ongoing => write!(f, "game in progress"),
+            GameStatus::Checkmate(true) => write!(f, "white wins by checkmate"),
+            GameStatus::Checkmate(false) => write!(f, "black wins by checkmate"),
+            GameStatus::Stalemate => write!(f, "draw by stalemate"),
+            GameStatus::InsufficientMaterial => write!(f, "draw by insufficient material"),
+            GameStatus::FivefoldRepetition => write!(f, "draw by fivefold repetition"),
+            GameStatus::SeventyFiveMove => write!(f, "draw by the seventy-five move rule"),
+            GameStatus::ThreefoldRepetition => write!(f, "draw by threefold repetition"),
+            GameStatus::FiftyMove => write!(f, "draw by the fifty move rule"),
+        }
+    }
+}
+
+impl BoardData {
+    pub fn status(&self) -> GameStatus {
+        let mut moves = Vec::new();
+        self.generate_legal_moves(&mut moves);
+        if moves.is_empty() {
+            return if self.in_check() {
+                GameStatus::Checkmate(!self.to_move)
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+
+        let repetitions = self.repetitions();
+        if self.is_insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else if repetitions >= 4 {
+            GameStatus::FivefoldRepetition
+        } else if self.half_move_counter >= 150 {
+            GameStatus::SeventyFiveMove
+        } else if repetitions >= 2 {
+            GameStatus::ThreefoldRepetition
+        } else if self.is_fifty_move_draw() {
+            GameStatus::FiftyMove
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameStatus;
+    use crate::fen::{from_fen, startpos};
+
+    fn status(fen: &str) -> GameStatus {
+        from_fen(fen).unwrap().status()
+    }
+
+    #[test]
+    fn mates_and_stalemates() {
+        assert_eq!(startpos().status(), GameStatus::Ongoing);
+        // fool's mate
+        assert_eq!(
+            status("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"),
+            GameStatus::Checkmate(false)
+        );
+        assert_eq!(
+            status("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"),
+            GameStatus::Checkmate(true)
+        );
+        assert_eq!(
+            status("k7/8/1Q6/8/8/8/8/7K b - - 0 1"),
+            GameStatus::Stalemate
+        );
+    }
+
+    #[test]
+    fn checkmate_beats_the_move_rules() {
+        assert_eq!(
+            status("R5k1/5ppp/8/8/8/8/8/6K1 b - - 150 90"),
+            GameStatus::Checkmate(true)
+        );
+    }
+
+    #[test]
+    fn move_rules() {
+        let fen = "8/8/4k3/8/8/3RK3/8/8 w - - ";
+        assert_eq!(status(&(fen.to_string() + "99 80")), GameStatus::Ongoing);
+        assert_eq!(status(&(fen.to_string() + "100 80")), GameStatus::FiftyMove);
+        assert_eq!(
+            status(&(fen.to_string() + "150 80")),
+            GameStatus::SeventyFiveMove
+        );
+        assert!(GameStatus::FiftyMove.is_claimable());
+        assert!(!GameStatus::SeventyFiveMove.is_claimable());
+    }
+
+    #[test]
+    fn repetitions() {
+        let mut board = startpos();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            for move_str in shuffle {
+                let action = board.parse_uci_move(move_str).unwrap();
+                board.make_move(action);
+            }
+            statuses.push(board.status());
+        }
+        assert_eq!(
+            statuses,
+            [
+                GameStatus::Ongoing,
+                GameStatus::ThreefoldRepetition,
+                GameStatus::ThreefoldRepetition,
+                GameStatus::FivefoldRepetition
+            ]
+        );
+    }
+
+    // the first occurrence is straight after a double push, with a passant
+    // square that no black pawn can use
+    #[test]
+    fn repetitions_after_a_double_push() {
+        let mut board = startpos();
+        let e4 = board.parse_uci_move("e2e4").unwrap();
+        board.make_move(e4);
+        let shuffle = ["g8f6", "g1f3", "f6g8", "f3g1"];
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            for move_str in shuffle {
+                let action = board.parse_uci_move(move_str).unwrap();
+                board.make_move(action);
+            }
+            statuses.push(board.status());
+        }
+        assert_eq!(
+            statuses,
+            [
+                GameStatus::Ongoing,
+                GameStatus::ThreefoldRepetition,
+                GameStatus::ThreefoldRepetition,
+                GameStatus::FivefoldRepetition
+            ]
+        );
+    }
+
+    #[test]
+    fn insufficient_material() {
+        assert_eq!(
+            status("8/8/4k3/8/8/3NK3/8/8 w - - 0 1"),
+            GameStatus::InsufficientMaterial
+        );
+        assert_eq!(
+            status("8/8/4k3/8/8/3PK3/8/8 w - - 0 1"),
+            GameStatus::Ongoing
+        );
+    }
+}
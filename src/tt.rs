@@ -0,0 +1,209 @@
+use std::mem::size_of;
+
+use crate::action::Action;
+use crate::search::MATE_BOUND;
+
+/* ========================================
+*   The transposition table remembers what
+|   was found about a position, indexed by
+*   the low bits of its zobrist key. The
+|   upper 32 bits are kept to tell apart
+*   positions sharing a slot. Mate scores
+|   are stored relative to the node, not
+*   the root, so they stay correct when the
+|   position is reached at another ply.
+   ======================================   */
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+pub const MAX_SIZE_MB: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // the score is at least this, the search failed high
+    Lower,
+    // the score is at most this, the search failed low
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TTEntry {
+    key: u32,
+    pub best_move: Option<Action>,
+    score: i16,
+    pub depth: u8,
+    pub bound: Bound,
+    age: u8,
+}
+
+impl TTEntry {
+    const EMPTY: TTEntry = TTEntry {
+        key: 0,
+        best_move: None,
+        score: 0,
+        depth: 0,
+        bound: Bound::Upper,
+        age: 0,
+    };
+
+    // the score as seen from the root, ply moves away
+    #[inline]
+    pub fn score(&self, ply: usize) -> i32 {
+        let score = self.score as i32;
+        if score >= MATE_BOUND {
+            score - ply as i32
+        } else if score <= -MATE_BOUND {
+            score + ply as i32
+        } else {
+            score
+        }
+    }
+}
+
+pub struct TranspositionTable {
+    entries: Vec<TTEntry>,
+    mask: usize,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let mut tt = TranspositionTable {
+            entries: Vec::new(),
+            mask: 0,
+            age: 0,
+        };
+        tt.resize(size_mb);
+        tt
+    }
+
+    // the largest power of two number of entries that fits in size_mb
+    pub fn resize(&mut self, size_mb: usize) {
+        let bytes = size_mb.clamp(1, MAX_SIZE_MB) * 1024 * 1024;
+        let count = 1 << (bytes / size_of::<TTEntry>()).ilog2();
+        self.entries = vec![TTEntry::EMPTY; count];
+        self.mask = count - 1;
+        self.age = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(TTEntry::EMPTY);
+        self.age = 0;
+    }
+
+    // entries from earlier searches are replaced first
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    #[inline]
+    fn index(&self, key: u64) -> usize {
+        key as usize & self.mask
+    }
+
+    #[inline]
+    fn verification(key: u64) -> u32 {
+        (key >> 32) as u32
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let entry = self.entries[self.index(key)];
+        if entry.depth > 0 && entry.key == Self::verification(key) {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    // score is relative to the root, ply is how far from the root this node is.
+    // depth must be at least 1, 0 marks an empty slot
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u8,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Action>,
+        ply: usize,
+    ) {
+        let index = self.index(key);
+        let verification = Self::verification(key);
+        let old = self.entries[index];
+        let same_position = old.key == verification && old.depth > 0;
+
+        // keep a deeper result from this search, unless the new one is exact
+        if old.age == self.age && old.depth > depth && bound != Bound::Exact {
+            return;
+        }
+
+        let score = if score >= MATE_BOUND {
+            score + ply as i32
+        } else if score <= -MATE_BOUND {
+            score - ply as i32
+        } else {
+            score
+        };
+        // a fail low has no best move, but an older one for this position is still useful
+        let best_move = match best_move {
+            None if same_position => old.best_move,
+            best_move => best_move,
+        };
+        self.entries[index] = TTEntry {
+            key: verification,
+            best_move,
+            score: score as i16,
+            depth,
+            bound,
+            age: self.age,
+        };
+    }
+
+    // permille of entries written during this search, for the uci "hashfull" info
+    pub fn hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        self.entries[..sample]
+            .iter()
+            .filter(|entry| entry.depth > 0 && entry.age == self.age)
+            .count()
+            * 1000
+            / sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE;
+
+    #[test]
+    fn size_is_power_of_two() {
+        let tt = TranspositionTable::new(3);
+        assert!(tt.entries.len().is_power_of_two());
+        assert!(tt.entries.len() * size_of::<TTEntry>() <= 3 * 1024 * 1024);
+    }
+
+    #[test]
+    fn stores_and_probes() {
+        let mut tt = TranspositionTable::new(1);
+        let key = 0x1234_5678_9abc_def0;
+        tt.store(key, 5, Bound::Lower, 42, Some(7), 3);
+
+        let entry = tt.probe(key).unwrap();
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert_eq!(entry.score(3), 42);
+        assert_eq!(entry.best_move, Some(7));
+
+        // same slot, different position
+        assert!(tt.probe(key ^ (1 << 40)).is_none());
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_node() {
+        let mut tt = TranspositionTable::new(1);
+        // mate in 5 plies from the root, found 2 plies in
+        tt.store(1, 4, Bound::Exact, MATE - 5, None, 2);
+        // reached again 4 plies in, the mate is now 7 plies from the root
+        assert_eq!(tt.probe(1).unwrap().score(4), MATE - 7);
+    }
+}
@@ -0,0 +1,419 @@
+use std::fmt;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::action::Move;
+use crate::board::BoardData;
+use crate::fen::{self, FenError};
+use crate::notation::MoveParseError;
+use crate::pawns::PawnTable;
+use crate::search::{mate_in_plies, Search, SearchLimits};
+use crate::tt::{self, TranspositionTable};
+
+const ENGINE_NAME: &str = "chess";
+const ENGINE_AUTHOR: &str = "AAce3";
+
+// time kept in reserve so that communication lag doesn't lose on time
+const MOVE_OVERHEAD: u64 = 50;
+
+/* ========================================
+*   The UCI front end reads commands from
+|   stdin and answers on stdout. Searches
+*   run on their own thread, so that "stop"
+|   and "isready" can still be answered
+*   while the engine is thinking.
+   ======================================   */
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GoParams {
+    pub depth: Option<u8>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub nodes: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    pub fn parse(tokens: &[&str]) -> GoParams {
+        let mut params = GoParams::default();
+        let mut tokens = tokens.iter();
+        while let Some(token) = tokens.next() {
+            let mut next_num = || tokens.next().and_then(|val| val.parse::<i64>().ok());
+            match *token {
+                "depth" => params.depth = next_num().map(|val| val.clamp(1, 255) as u8),
+                "movetime" => params.movetime = next_num().map(|val| val.max(0) as u64),
+                "wtime" => params.wtime = next_num().map(|val| val.max(0) as u64),
+                "btime" => params.btime = next_num().map(|val| val.max(0) as u64),
+                "winc" => params.winc = next_num().map(|val| val.max(0) as u64),
+                "binc" => params.binc = next_num().map(|val| val.max(0) as u64),
+                "movestogo" => params.movestogo = next_num().map(|val| val.max(1) as u64),
+                "nodes" => params.nodes = next_num().map(|val| val.max(0) as u64),
+                "infinite" => params.infinite = true,
+                _ => (),
+            }
+        }
+        params
+    }
+
+    // how long to think for, in milliseconds. None means no time limit
+    pub fn time_for_move(&self, white_to_move: bool) -> Option<u64> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime.saturating_sub(MOVE_OVERHEAD).max(1));
+        }
+        let (time, inc) = if white_to_move {
+            (self.wtime?, self.winc.unwrap_or(0))
+        } else {
+            (self.btime?, self.binc.unwrap_or(0))
+        };
+        let movestogo = self.movestogo.unwrap_or(30);
+        let budget = time / movestogo + inc * 3 / 4;
+        let available = time.saturating_sub(MOVE_OVERHEAD);
+        Some(budget.min(available).max(1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionCommandError {
+    MissingPosition,
+    InvalidFen(FenError),
+    InvalidMove(MoveParseError),
+}
+
+impl fmt::Display for PositionCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionCommandError::MissingPosition => write!(f, "expected startpos or fen"),
+            PositionCommandError::InvalidFen(error) => write!(f, "invalid fen: {}", error),
+            PositionCommandError::InvalidMove(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PositionCommandError {}
+
+// the arguments of "position": startpos or fen <fields>, then optionally moves <moves>
+pub fn parse_position(args: &[&str]) -> Result<BoardData, PositionCommandError> {
+    let moves_idx = args.iter().position(|token| *token == "moves");
+    let (position, moves) = match moves_idx {
+        Some(idx) => (&args[..idx], &args[idx + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let mut board = match position.split_first() {
+        Some((&"startpos", _)) => fen::startpos(),
+        Some((&"fen", fen_fields)) => {
+            fen::from_fen(&fen_fields.join(" ")).map_err(PositionCommandError::InvalidFen)?
+        }
+        _ => return Err(PositionCommandError::MissingPosition),
+    };
+    for move_str in moves {
+        let action = board
+            .parse_uci_move(move_str)
+            .map_err(PositionCommandError::InvalidMove)?;
+        board.make_move(action);
+    }
+    Ok(board)
+}
+
+pub struct Uci {
+    // None after a position command that failed, until a valid one arrives
+    board: Option<BoardData>,
+    // shared with the search thread, which holds the locks while it searches
+    tt: Arc<Mutex<TranspositionTable>>,
+    pawn_table: Arc<Mutex<PawnTable>>,
+    stop: Arc<AtomicBool>,
+    search_thread: Option<JoinHandle<()>>,
+}
+
+impl Uci {
+    pub fn new() -> Uci {
+        Uci {
+            board: Some(fen::startpos()),
+            tt: Arc::new(Mutex::new(TranspositionTable::new(tt::DEFAULT_SIZE_MB))),
+            pawn_table: Arc::new(Mutex::new(PawnTable::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            search_thread: None,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if !self.handle_command(&line) {
+                break;
+            }
+        }
+        self.stop_search();
+    }
+
+    // returns false once the engine should quit
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match tokens.split_first() {
+            Some(split) => split,
+            None => return true,
+        };
+
+        match *command {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    tt::DEFAULT_SIZE_MB,
+                    tt::MAX_SIZE_MB
+                );
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.board = Some(fen::startpos());
+                lock(&self.tt).clear();
+                lock(&self.pawn_table).clear();
+            }
+            "position" => {
+                self.stop_search();
+                self.set_position(args);
+            }
+            "go" => {
+                self.stop_search();
+                self.go(GoParams::parse(args));
+            }
+            "stop" => self.stop_search(),
+            "setoption" => {
+                self.stop_search();
+                self.set_option(args);
+            }
+            "quit" => return false,
+            _ => println!("info string unknown command {}", command),
+        }
+        true
+    }
+
+    // a bad position leaves no position at all, rather than the previous
+    // one or half of the moves, so that "go" can't search the wrong board
+    fn set_position(&mut self, args: &[&str]) {
+        self.board = match parse_position(args) {
+            Ok(board) => Some(board),
+            Err(e) => {
+                println!("info string {}", e);
+                None
+            }
+        };
+    }
+
+    fn set_option(&mut self, args: &[&str]) {
+        // setoption name <id> [value <x>]
+        let value_idx = args.iter().position(|token| *token == "value");
+        let name = match value_idx {
+            Some(idx) => args.get(1..idx),
+            None => args.get(1..),
+        }
+        .map(|name| name.join(" "))
+        .unwrap_or_default();
+        let value = value_idx.and_then(|idx| args.get(idx + 1));
+
+        if name.eq_ignore_ascii_case("Hash") {
+            match value.and_then(|val| val.parse::<usize>().ok()) {
+                Some(size_mb) => lock(&self.tt).resize(size_mb),
+                None => println!("info string invalid value for Hash"),
+            }
+        } else {
+            println!("info string unknown option {}", name);
+        }
+    }
+
+    fn go(&mut self, params: GoParams) {
+        let board = match &self.board {
+            Some(board) => board.clone(),
+            None => {
+                println!("info string no valid position to search");
+                println!("bestmove 0000");
+                return;
+            }
+        };
+        self.stop.store(false, Ordering::SeqCst);
+        let stop = Arc::clone(&self.stop);
+        let tt = Arc::clone(&self.tt);
+        let pawn_table = Arc::clone(&self.pawn_table);
+        self.search_thread = Some(thread::spawn(move || {
+            think(board, params, stop, tt, pawn_table)
+        }));
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.search_thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn think(
+    mut board: BoardData,
+    params: GoParams,
+    stop: Arc<AtomicBool>,
+    tt: Arc<Mutex<TranspositionTable>>,
+    pawn_table: Arc<Mutex<PawnTable>>,
+) {
+    let mut tt = lock(&tt);
+    let mut pawn_table = lock(&pawn_table);
+    let limits = SearchLimits {
+        depth: params.depth,
+        time: params
+            .time_for_move(board.to_move)
+            .map(Duration::from_millis),
+        nodes: params.nodes,
+    };
+    let result =
+        Search::new(limits, Arc::clone(&stop), &mut tt, &mut pawn_table).run(&mut board, |info| {
+            let millis = info.elapsed.as_millis() as u64;
+            let pv: Vec<String> = info.pv.iter().map(|action| action.to_uci()).collect();
+            println!(
+                "info depth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+                info.depth,
+                format_score(info.score),
+                info.nodes,
+                info.nodes * 1000 / millis.max(1),
+                info.hashfull,
+                millis,
+                pv.join(" ")
+            );
+        });
+
+    // the gui expects no bestmove until it sends "stop" during an infinite search
+    while params.infinite && !stop.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    match result.best_move {
+        Some(action) => println!("bestmove {}", action.to_uci()),
+        None => println!("bestmove 0000"),
+    }
+}
+
+// a search thread that panicked leaves the lock poisoned, the table is still usable
+fn lock<T>(table: &Mutex<T>) -> MutexGuard<'_, T> {
+    table
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// "cp 35", or "mate 3" / "mate -2" counted in moves rather than plies
+fn format_score(score: i32) -> String {
+    match mate_in_plies(score) {
+        Some(plies) if plies > 0 => format!("mate {}", (plies + 1) / 2),
+        Some(plies) => format!("mate {}", plies / 2),
+        None => format!("cp {}", score),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn go(command: &str) -> GoParams {
+        GoParams::parse(&command.split_whitespace().collect::<Vec<_>>())
+    }
+
+    fn position(command: &str) -> Result<BoardData, PositionCommandError> {
+        parse_position(&command.split_whitespace().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn go_parameters() {
+        let params =
+            go("wtime 60000 btime 30000 winc 1000 binc 500 movestogo 20 depth 8 nodes 5000");
+        assert_eq!(params.wtime, Some(60000));
+        assert_eq!(params.btime, Some(30000));
+        assert_eq!(params.winc, Some(1000));
+        assert_eq!(params.binc, Some(500));
+        assert_eq!(params.movestogo, Some(20));
+        assert_eq!(params.depth, Some(8));
+        assert_eq!(params.nodes, Some(5000));
+        assert!(!params.infinite);
+
+        assert!(go("infinite").infinite);
+        assert_eq!(go("depth 0").depth, Some(1));
+        assert_eq!(go("wtime -20").wtime, Some(0));
+        assert_eq!(go("movetime abc").movetime, None);
+    }
+
+    #[test]
+    fn time_for_move() {
+        // a fixed time, less the overhead
+        assert_eq!(go("movetime 1000").time_for_move(true), Some(950));
+        assert_eq!(go("movetime 10").time_for_move(true), Some(1));
+        // a thirtieth of the clock plus most of the increment, for the side to move
+        let clock = go("wtime 60000 btime 30000 winc 1000 binc 400");
+        assert_eq!(clock.time_for_move(true), Some(2000 + 750));
+        assert_eq!(clock.time_for_move(false), Some(1000 + 300));
+        assert_eq!(
+            go("wtime 60000 movestogo 1").time_for_move(true),
+            Some(59950)
+        );
+        // never more than is left on the clock
+        assert_eq!(go("wtime 40 winc 1000").time_for_move(true), Some(1));
+        assert_eq!(go("infinite movetime 1000").time_for_move(true), None);
+        assert_eq!(go("depth 5").time_for_move(true), None);
+        assert_eq!(go("btime 1000").time_for_move(true), None);
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(position("startpos").unwrap().to_fen(), fen::STARTPOS);
+        assert_eq!(
+            position("startpos moves e2e4 e7e5 g1f3").unwrap().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(
+            position("fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1")
+                .unwrap()
+                .to_fen(),
+            "4k3/8/8/8/8/8/8/5RK1 b - - 1 1"
+        );
+
+        assert!(matches!(
+            position(""),
+            Err(PositionCommandError::MissingPosition)
+        ));
+        assert!(matches!(
+            position("fen 8/8 w"),
+            Err(PositionCommandError::InvalidFen(_))
+        ));
+        assert!(matches!(
+            position("startpos moves e2e4 e2e4"),
+            Err(PositionCommandError::InvalidMove(
+                MoveParseError::IllegalMove(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn bad_position_clears_the_board() {
+        let mut uci = Uci::new();
+        uci.handle_command("position fen 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+        assert!(uci.board.is_some());
+        uci.handle_command("position fen 4k3/8/8/8/8/8/8/R3K3 x Q - 0 1");
+        assert!(uci.board.is_none());
+        uci.handle_command("position startpos moves e2e4 e7e5 e1e3");
+        assert!(uci.board.is_none());
+        uci.handle_command("ucinewgame");
+        assert!(uci.board.is_some());
+    }
+}
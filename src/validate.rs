@@ -0,0 +1,181 @@
+use std::fmt;
+
+use crate::bit_operations::{generate_from_index, pop_count};
+use crate::board::{pieces, BoardData, BK, BQ, WK, WQ};
+use crate::fen::idx_to_coordsquare;
+
+/* ========================================
+*   Semantic checks for positions that are
+|   well-formed but that make_move can't
+*   handle, such as a missing king or
+|   castle rights without a rook. These
+*   can't come up through make_move, only
+|   from outside input like a FEN.
+   ======================================   */
+
+const BACK_RANKS: u64 = 0xff000000000000ff;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionError {
+    WrongKingCount { white: u8, black: u8 },
+    PawnOnBackRank(u8),
+    InvalidCastlingRights(u8),
+    InvalidPassantSquare(u8),
+    OpponentInCheck,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::WrongKingCount { white, black } => write!(
+                f,
+                "each side needs exactly one king, found {} white and {} black",
+                white, black
+            ),
+            PositionError::PawnOnBackRank(sqr) => {
+                write!(
+                    f,
+                    "pawn on the first or last rank at {}",
+                    idx_to_coordsquare(*sqr)
+                )
+            }
+            PositionError::InvalidCastlingRights(mask) => write!(
+                f,
+                "castling rights {:04b} without king and rook on their home squares",
+                mask
+            ),
+            PositionError::InvalidPassantSquare(sqr) => write!(
+                f,
+                "en passant square {} without a double pushed pawn",
+                idx_to_coordsquare(*sqr)
+            ),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl BoardData {
+    pub fn validate(&self) -> Result<(), PositionError> {
+        let white_kings = pop_count(self.bitboards[pieces::WKING as usize]);
+        let black_kings = pop_count(self.bitboards[pieces::BKING as usize]);
+        if white_kings != 1 || black_kings != 1 {
+            return Err(PositionError::WrongKingCount {
+                white: white_kings,
+                black: black_kings,
+            });
+        }
+
+        let pawns = self.bitboards[pieces::WPAWN as usize] | self.bitboards[pieces::BPAWN as usize];
+        if pawns & BACK_RANKS != 0 {
+            return Err(PositionError::PawnOnBackRank(
+                (pawns & BACK_RANKS).trailing_zeros() as u8,
+            ));
+        }
+
+        // each right needs the king and the rook it castles with still at home
+        let castle_requirements = [
+            (WK, pieces::WKING, 4, pieces::WROOK, 7),
+            (WQ, pieces::WKING, 4, pieces::WROOK, 0),
+            (BK, pieces::BKING, 60, pieces::BROOK, 63),
+            (BQ, pieces::BKING, 60, pieces::BROOK, 56),
+        ];
+        for (right, king, king_square, rook, rook_square) in castle_requirements {
+            if self.castle_rights_mask & right != 0
+                && (self.mailbox[king_square] != king || self.mailbox[rook_square] != rook)
+            {
+                return Err(PositionError::InvalidCastlingRights(
+                    self.castle_rights_mask,
+                ));
+            }
+        }
+
+        if let Some(sqr) = self.passant_square {
+            // the pawn that just double pushed, and the square it came from
+            let (expected_rank, pawn_square, from_square, pawn) = if self.to_move {
+                (5, sqr.wrapping_sub(8), sqr.wrapping_add(8), pieces::BPAWN)
+            } else {
+                (2, sqr.wrapping_add(8), sqr.wrapping_sub(8), pieces::WPAWN)
+            };
+            if sqr >> 3 != expected_rank
+                || self.mailbox[sqr as usize] != pieces::NOPIECE
+                || self.mailbox[from_square as usize] != pieces::NOPIECE
+                || self.bitboards[pawn as usize] & generate_from_index(pawn_square) == 0
+            {
+                return Err(PositionError::InvalidPassantSquare(sqr));
+            }
+        }
+
+        if self.is_square_attacked(self.king_square(!self.to_move), self.to_move) {
+            return Err(PositionError::OpponentInCheck);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PositionError;
+    use crate::fen::{from_fen, FenError};
+
+    fn position_error(fen: &str) -> Option<PositionError> {
+        match from_fen(fen) {
+            Ok(_) => None,
+            Err(FenError::InvalidPosition(error)) => Some(error),
+            Err(error) => panic!("{} is not a well-formed fen: {}", fen, error),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        let cases = [
+            (
+                "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+                PositionError::WrongKingCount { white: 2, black: 1 },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/P3K3 w - - 0 1",
+                PositionError::PawnOnBackRank(0),
+            ),
+            (
+                "p3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                PositionError::PawnOnBackRank(56),
+            ),
+            // no rook on h1
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                PositionError::InvalidCastlingRights(0b1000),
+            ),
+            // white just moved, but it's white to move
+            (
+                "4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1",
+                PositionError::InvalidPassantSquare(20),
+            ),
+            // no pawn on e4 that could have come from e2
+            (
+                "4k3/8/8/8/8/8/8/4K3 b - e3 0 1",
+                PositionError::InvalidPassantSquare(20),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1",
+                PositionError::OpponentInCheck,
+            ),
+        ];
+        for (fen, error) in cases {
+            assert_eq!(position_error(fen), Some(error), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn accepts_valid_positions() {
+        for fen in [
+            "4k3/8/8/8/4P3/8/8/4K2R b K e3 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            // the side to move may be in check
+            "4k3/8/8/8/8/8/8/4R1K1 b - - 0 1",
+        ] {
+            assert_eq!(position_error(fen), None, "{}", fen);
+        }
+    }
+}